@@ -0,0 +1,246 @@
+use std::error::Error;
+use std::path::PathBuf;
+use voxel_generator::Ktx2Format;
+use voxel_generator::preview::Sampling;
+use voxel_generator::scene::ExportFormat;
+
+pub const USAGE: &str = "Usage: voxel_generator <command> [options]
+
+Commands:
+  bake <scene>              Bake every tile in a scene file
+  mesh <scene>              Mesh every tile in a scene file without lighting
+  palette                   Write the palette as a colormap image
+  preview <scene>           Write a top down preview image of every tile
+  render <scene>            Bake every tile and render it from the scene camera
+  sheet <scene>             Bake every tile and render it from several angles into one
+                            contact sheet image
+  compare <scene>           Check the solver against a path traced reference for every
+                            tile, lit by its emitters, lights, sky and sun, and write a
+                            report next to it
+
+Options:
+  -o, --output <path>       Directory to write tiles to, or the image path for `palette`
+  --iterations <count>      Number of light bounces to simulate
+  --subdivisions <count>    Times every face is split into four before baking
+  --format <format>         Output format, one of: glb, gltf, gltf-embedded, obj,
+                            ply, ply-ascii
+  --palette <palette>       `default` or the path of a colormap image
+  --ktx2 <format>           Also write textures as KTX2, one of: rgba8, bc1
+  --mipmaps                 Sample lightmaps with per face mip levels from KTX2 files
+  --unlit                   Mark materials as unlit, so viewers show the baked light as is
+  --combine <path>          Write every tile into one file, glb or gltf-embedded only
+  --sampling <sampling>     Texture filtering for `render`, one of: nearest, bilinear
+  --angles <count>          Number of angles every tile is shown from by `sheet`
+  --samples <count>         Paths traced from every face by `compare`
+  -h, --help                Print this message";
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Command {
+    Bake(PathBuf),
+    Mesh(PathBuf),
+    Palette,
+    Preview(PathBuf),
+    Render(PathBuf),
+    Sheet(PathBuf),
+    Compare(PathBuf),
+    Help,
+}
+
+/// Options overriding the ones in the scene file.
+#[derive(Debug, Default)]
+pub struct Options {
+    pub output: Option<PathBuf>,
+    pub iterations: Option<u8>,
+    pub subdivisions: Option<u8>,
+    pub format: Option<ExportFormat>,
+    pub palette: Option<String>,
+    pub combine: Option<PathBuf>,
+    pub unlit: bool,
+    pub mipmaps: bool,
+    pub ktx2: Option<Ktx2Format>,
+    pub sampling: Option<Sampling>,
+    pub angles: Option<u8>,
+    pub samples: Option<u32>,
+}
+
+fn parse_count(name: &str, value: String) -> Result<u8, String> {
+    value.parse().map_err(|_| format!("{} expects a number from 0 to 255, got `{}`", name, value))
+}
+
+fn parse_samples(name: &str, value: String) -> Result<u32, String> {
+    value.parse().ok().filter(|samples| *samples > 0).ok_or_else(|| format!("{} expects a positive number, got `{}`", name, value))
+}
+
+/// Parses the arguments, without the program name, into a command and its options.
+pub fn parse_args(args: impl IntoIterator<Item=String>) -> Result<(Command, Options), String> {
+    let mut options = Options::default();
+    let mut positional = Vec::new();
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or_else(|| format!("{} expects a value", name));
+        match arg.as_str() {
+            "-h" | "--help" => return Ok((Command::Help, options)),
+            "-o" | "--output" => options.output = Some(PathBuf::from(value(&arg)?)),
+            "--iterations" => options.iterations = Some(parse_count(&arg, value(&arg)?)?),
+            "--subdivisions" => options.subdivisions = Some(parse_count(&arg, value(&arg)?)?),
+            "--format" => {
+                let format = value(&arg)?;
+                options.format = Some(ExportFormat::from_name(&format).ok_or_else(|| format!("unknown format `{}`", format))?);
+            }
+            "--palette" => options.palette = Some(value(&arg)?),
+            "--unlit" => options.unlit = true,
+            "--mipmaps" => options.mipmaps = true,
+            "--ktx2" => {
+                let format = value(&arg)?;
+                options.ktx2 = Some(Ktx2Format::from_name(&format).ok_or_else(|| format!("unknown KTX2 format `{}`", format))?);
+            }
+            "--combine" => options.combine = Some(PathBuf::from(value(&arg)?)),
+            "--angles" => options.angles = Some(parse_count(&arg, value(&arg)?)?),
+            "--samples" => options.samples = Some(parse_samples(&arg, value(&arg)?)?),
+            "--sampling" => {
+                let sampling = value(&arg)?;
+                options.sampling = Some(Sampling::from_name(&sampling).ok_or_else(|| format!("unknown sampling `{}`", sampling))?);
+            }
+            _ if arg.starts_with('-') => return Err(format!("unknown option `{}`", arg)),
+            _ => positional.push(arg),
+        }
+    }
+
+    let mut positional = positional.into_iter();
+    let command = match positional.next().as_deref() {
+        None => return Err("missing command".to_string()),
+        Some("bake") => Command::Bake(positional.next().ok_or("bake expects a scene file")?.into()),
+        Some("mesh") => Command::Mesh(positional.next().ok_or("mesh expects a scene file")?.into()),
+        Some("palette") => Command::Palette,
+        Some("preview") => Command::Preview(positional.next().ok_or("preview expects a scene file")?.into()),
+        Some("render") => Command::Render(positional.next().ok_or("render expects a scene file")?.into()),
+        Some("sheet") => Command::Sheet(positional.next().ok_or("sheet expects a scene file")?.into()),
+        Some("compare") => Command::Compare(positional.next().ok_or("compare expects a scene file")?.into()),
+        Some(command) => return Err(format!("unknown command `{}`", command)),
+    };
+    if let Some(extra) = positional.next() {
+        return Err(format!("unexpected argument `{}`", extra));
+    }
+    Ok((command, options))
+}
+
+/// Exit code for arguments that can't be parsed, after printing the usage.
+pub const USAGE_ERROR: u8 = 2;
+/// Exit code for a command that failed.
+pub const FAILURE: u8 = 1;
+
+/// An error followed by the errors that caused it, like `Save Mesh Error: IO Error: ...`.
+pub fn error_message(error: &dyn Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(error) = source {
+        message += &format!(": {}", error);
+        source = error.source();
+    }
+    message
+}
+
+/// Parses the arguments and runs the command with `run`, printing any error, and returns the
+/// exit code.
+pub fn run_cli<E: Error>(args: impl IntoIterator<Item=String>, run: impl FnOnce(Command, Options) -> Result<(), E>) -> u8 {
+    let (command, options) = match parse_args(args) {
+        Ok(parsed) => parsed,
+        Err(message) => {
+            eprintln!("error: {}\n\n{}", message, USAGE);
+            return USAGE_ERROR;
+        }
+    };
+
+    match run(command, options) {
+        Ok(()) => 0,
+        Err(error) => {
+            eprintln!("error: {}", error_message(&error));
+            FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<(Command, Options), String> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    fn exit_code(args: &[&str], result: Result<(), io::Error>) -> (u8, bool) {
+        let mut ran = false;
+        let code = run_cli(args.iter().map(|arg| arg.to_string()), |_, _| {
+            ran = true;
+            result
+        });
+        (code, ran)
+    }
+
+    #[test]
+    fn parses_commands_and_options() {
+        let (command, options) = parse(&["bake", "scene.json", "-o", "out", "--iterations", "2", "--format", "obj", "--unlit"]).unwrap();
+        assert_eq!(command, Command::Bake(PathBuf::from("scene.json")));
+        assert_eq!(options.output, Some(PathBuf::from("out")));
+        assert_eq!(options.iterations, Some(2));
+        assert_eq!(options.format, Some(ExportFormat::Obj));
+        assert!(options.unlit);
+
+        // Options may come before the command too
+        let (command, options) = parse(&["--samples", "16", "compare", "scene.json"]).unwrap();
+        assert_eq!(command, Command::Compare(PathBuf::from("scene.json")));
+        assert_eq!(options.samples, Some(16));
+        assert_eq!(parse(&["palette"]).unwrap().0, Command::Palette);
+    }
+
+    #[test]
+    fn help_wins_over_everything_else() {
+        assert_eq!(parse(&["--help"]).unwrap().0, Command::Help);
+        assert_eq!(parse(&["bake", "-h", "--bogus"]).unwrap().0, Command::Help);
+    }
+
+    #[test]
+    fn rejects_unknown_options_and_commands() {
+        assert_eq!(parse(&["bake", "scene.json", "--bogus"]).unwrap_err(), "unknown option `--bogus`");
+        assert_eq!(parse(&["frobnicate"]).unwrap_err(), "unknown command `frobnicate`");
+        assert_eq!(parse(&[]).unwrap_err(), "missing command");
+        assert_eq!(parse(&["bake", "a.json", "b.json"]).unwrap_err(), "unexpected argument `b.json`");
+    }
+
+    #[test]
+    fn rejects_missing_values() {
+        assert_eq!(parse(&["bake", "scene.json", "--output"]).unwrap_err(), "--output expects a value");
+        assert_eq!(parse(&["bake", "scene.json", "--iterations"]).unwrap_err(), "--iterations expects a value");
+        assert_eq!(parse(&["bake"]).unwrap_err(), "bake expects a scene file");
+        assert_eq!(parse(&["render"]).unwrap_err(), "render expects a scene file");
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        assert_eq!(parse(&["--iterations", "300", "bake", "a.json"]).unwrap_err(), "--iterations expects a number from 0 to 255, got `300`");
+        assert_eq!(parse(&["--samples", "0", "compare", "a.json"]).unwrap_err(), "--samples expects a positive number, got `0`");
+        assert_eq!(parse(&["--format", "fbx", "bake", "a.json"]).unwrap_err(), "unknown format `fbx`");
+        assert_eq!(parse(&["--ktx2", "astc", "bake", "a.json"]).unwrap_err(), "unknown KTX2 format `astc`");
+        assert_eq!(parse(&["--sampling", "cubic", "render", "a.json"]).unwrap_err(), "unknown sampling `cubic`");
+    }
+
+    #[test]
+    fn exit_codes() {
+        assert_eq!(exit_code(&["bake", "scene.json"], Ok(())), (0, true));
+        assert_eq!(exit_code(&["bake", "scene.json"], Err(io::Error::other("missing"))), (FAILURE, true));
+        // Arguments that can't be parsed don't run anything
+        assert_eq!(exit_code(&["bake", "--bogus"], Ok(())), (USAGE_ERROR, false));
+        assert_eq!(exit_code(&[], Ok(())), (USAGE_ERROR, false));
+    }
+
+    #[test]
+    fn error_messages_include_their_sources() {
+        #[derive(Debug, thiserror::Error)]
+        #[error("Outer Error")]
+        struct Outer(#[source] io::Error);
+
+        assert_eq!(error_message(&Outer(io::Error::other("disk full"))), "Outer Error: disk full");
+    }
+}
@@ -1,9 +1,12 @@
-use json::{JsonError, object, array};
+use json::{JsonError, JsonValue, object, array};
+use std::collections::HashMap;
 use std::fs::File;
-use std::io::{Cursor, Seek, SeekFrom, Write};
+use std::io::{Cursor, Write};
 use std::{fs, result};
-use image::{DynamicImage, ImageError, RgbaImage};
-use crate::vector::{Vec2, Vec3};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use image::{DynamicImage, ImageError};
+use crate::mesh::{BakedMesh, MeshTexture};
 use thiserror::{Error};
 
 fn float_max<T>(it: T) -> f32 where T: Iterator<Item=f32> {
@@ -17,10 +20,18 @@ fn float_min<T>(it: T) -> f32 where T: Iterator<Item=f32> {
 }
 
 fn pad_length(x: usize) -> usize {
-    ((x + 3) / 4) * 4
+    x.div_ceil(4) * 4
 }
 
+const GLB_MAGIC: u32 = 0x46546C67; // "glTF"
+const GLB_VERSION: u32 = 2;
+const GLB_JSON_CHUNK: u32 = 0x4E4F534A; // "JSON"
+const GLB_BIN_CHUNK: u32 = 0x004E4942; // "BIN\0"
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+
 #[derive(Debug, Error)]
+#[allow(clippy::enum_variant_names)]
 pub enum SaveMeshError {
     #[error("Json Error")]
     JsonError(#[from] JsonError),
@@ -28,16 +39,206 @@ pub enum SaveMeshError {
     IOError(#[from] std::io::Error),
     #[error("Image Error")]
     ImageError(#[from] ImageError),
+    /// A node has a mesh without faces, which glTF has no valid way to store.
+    #[error("Empty mesh: {0}")]
+    EmptyMeshError(String),
+}
+
+/// Appends `suffix` to a path, unlike [`Path::with_extension`] this keeps any dots in the name.
+pub fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = OsString::from(path);
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+/// Encodes the texture of a mesh as PNG, or nothing when it is external.
+pub fn texture_png(texture: &MeshTexture) -> result::Result<Vec<u8>, SaveMeshError> {
+    match texture {
+        MeshTexture::Embedded(image) => png(image),
+        MeshTexture::External(_) => Ok(Vec::new()),
+    }
+}
+
+fn png(image: &DynamicImage) -> result::Result<Vec<u8>, SaveMeshError> {
+    let mut image_bytes: Vec<u8> = Vec::new();
+    image.write_to(&mut Cursor::new(&mut image_bytes), image::ImageOutputFormat::Png)?;
+    Ok(image_bytes)
+}
+
+/// The images stored with a mesh, each with the suffix of the file it is saved to next to the mesh.
+pub(crate) fn embedded_images(mesh: &BakedMesh) -> Vec<(&'static str, &DynamicImage)> {
+    let mut images = Vec::new();
+    if let MeshTexture::Embedded(image) = &mesh.texture {
+        images.push(("", image));
+    }
+    if let Some(light) = &mesh.light {
+        images.push(("_light", light));
+    }
+    if let Some(emissive) = &mesh.emissive {
+        images.push(("_emissive", &emissive.texture));
+    }
+    if let Some(occlusion) = &mesh.occlusion {
+        images.push(("_occlusion", occlusion));
+    }
+    images
+}
+
+/// Saves the images stored with a mesh next to it as `{filename}{suffix}.png`.
+fn save_embedded_images(mesh: &BakedMesh, filename: &Path) -> result::Result<(), SaveMeshError> {
+    for (suffix, image) in embedded_images(mesh) {
+        let mut img_file = File::create(with_suffix(filename, &format!("{}.png", suffix)))?;
+        img_file.write_all(&png(image)?)?;
+    }
+    Ok(())
+}
+
+const BASE64_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let value = (chunk[0] as u32) << 16 | (*chunk.get(1).unwrap_or(&0) as u32) << 8 | *chunk.get(2).unwrap_or(&0) as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(BASE64_ALPHABET[(value >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// Escapes a file name for use as a relative URI.
+fn uri_encode(name: &str) -> String {
+    name.bytes().map(|byte| match byte {
+        b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (byte as char).to_string(),
+        _ => format!("%{:02X}", byte),
+    }).collect()
+}
+
+/// Escapes a relative path for use as a relative URI, keeping its directories.
+fn uri_encode_path(path: &str) -> String {
+    path.split(['/', '\\']).map(uri_encode).collect::<Vec<_>>().join("/")
+}
+
+/// How a node is placed in its scene.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Transform {
+    pub translation: [f32; 3],
+    /// A unit quaternion as `[x, y, z, w]`.
+    pub rotation: [f32; 4],
+    pub scale: [f32; 3],
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform {
+            translation: [0.; 3],
+            rotation: [0., 0., 0., 1.],
+            scale: [1.; 3],
+        }
+    }
+}
+
+/// A baked mesh placed in a scene with several meshes.
+pub struct SceneNode<'a> {
+    pub name: &'a str,
+    pub mesh: &'a BakedMesh,
+    pub transform: Transform,
+    /// Path of the KTX2 files of the mesh relative to the glTF file, without their suffix and
+    /// extension, which are referenced for their mip levels when the mesh has
+    /// [`BakedMesh::mipmaps`].
+    pub ktx2: Option<&'a str>,
+}
+
+/// The name of the node single mesh files are written with when they aren't given one.
+const DEFAULT_NODE_NAME: &str = "curve";
+
+/// The node single mesh files are written with.
+fn single_node<'a>(mesh: &'a BakedMesh, name: &'a str, transform: Transform, ktx2: Option<&'a str>) -> [SceneNode<'a>; 1] {
+    [SceneNode { name, mesh, transform, ktx2 }]
+}
+
+/// How the embedded textures of the nodes are stored, in order of the nodes.
+enum ImageStorage<'a> {
+    /// At the end of the binary buffer, taking this many bytes each.
+    Buffer(&'a [usize]),
+    /// At their own URIs.
+    Uri(&'a [String]),
+}
+
+/// The [`embedded_images`] of the nodes encoded as PNG, in order of the nodes.
+fn embedded_pngs(nodes: &[SceneNode]) -> result::Result<Vec<Vec<u8>>, SaveMeshError> {
+    nodes.iter()
+        .flat_map(|node| embedded_images(node.mesh))
+        .map(|(_, image)| png(image))
+        .collect()
+}
+
+/// Adds a buffer view of `length` bytes at the next 4 byte aligned offset, returning its index.
+fn push_buffer_view(document: &mut JsonValue, byte_offset: &mut usize, length: usize, stride: Option<usize>, target: Option<u32>) -> result::Result<usize, SaveMeshError> {
+    *byte_offset = pad_length(*byte_offset);
+    let mut view = object!{
+        "buffer"=>0,
+        "byteOffset"=>*byte_offset,
+        "byteLength"=>length,
+    };
+    if let Some(stride) = stride {
+        view["byteStride"] = stride.into();
+    }
+    if let Some(target) = target {
+        view["target"] = target.into();
+    }
+    document["bufferViews"].push(view)?;
+    *byte_offset += length;
+    Ok(document["bufferViews"].len() - 1)
+}
+
+/// Adds an accessor and returns its index.
+fn push_accessor(document: &mut JsonValue, accessor: JsonValue) -> result::Result<usize, SaveMeshError> {
+    document["accessors"].push(accessor)?;
+    Ok(document["accessors"].len() - 1)
+}
+
+/// All vertex data of a mesh, in the order the buffer views of [`gltf_document`] expect, with
+/// every view and the end of the buffer 4 byte aligned.
+fn vertex_buffer(mesh: &BakedMesh) -> Vec<u8> {
+    let buffer_normals = mesh.mesh.normals
+        .iter()
+        .flat_map(|x| [x.x.to_le_bytes(), x.y.to_le_bytes(), x.z.to_le_bytes()])
+        .flatten();
+    let buffer_positions = mesh.mesh.positions.iter().flat_map(|x| [x.x.to_le_bytes(), x.y.to_le_bytes(), x.z.to_le_bytes()]).flatten();
+    let buffer_uvs = mesh.mesh.texture_coordinates.iter().flat_map(|x| [x.x.to_le_bytes(), x.y.to_le_bytes()]).flatten();
+    let buffer_lightmap_uvs = mesh.mesh.lightmap_coordinates.iter().flat_map(|x| [x.x.to_le_bytes(), x.y.to_le_bytes()]).flatten();
+    let buffer_indices = mesh.mesh.indices.iter().flat_map(|x| (*x as u32).to_le_bytes());
+    let buffer_colors = mesh.colors.iter().flatten().flatten().flat_map(|x| x.to_le_bytes());
+
+    let mut buffer = Vec::new();
+    for section in [buffer_normals.collect::<Vec<u8>>(), buffer_positions.collect(), buffer_uvs.collect(), buffer_lightmap_uvs.collect(), buffer_indices.collect(), buffer_colors.collect()] {
+        buffer.extend(section);
+        buffer.resize(pad_length(buffer.len()), 0);
+    }
+    buffer
+}
+
+/// The binary buffer of a scene, the [`vertex_buffer`] of every node followed by the images.
+fn scene_buffer(nodes: &[SceneNode], images: &[Vec<u8>]) -> Vec<u8> {
+    let mut buffer: Vec<u8> = nodes.iter().flat_map(|node| vertex_buffer(node.mesh)).collect();
+    for image in images {
+        buffer.resize(pad_length(buffer.len()), 0);
+        buffer.extend_from_slice(image);
+    }
+    buffer
 }
 
-pub fn save_mesh(
-    filename: String,
-    vertexes: &Vec<Vec3>,
-    normals: &Vec<Vec3>,
-    uvs: &Vec<Vec2>,
-    indices: &Vec<usize>,
-    texture: DynamicImage,
-) -> result::Result<(), SaveMeshError> {
+/// Adds the accessors of a mesh, returning the attributes and the indices accessor of its primitive.
+fn push_mesh_accessors(document: &mut JsonValue, byte_offset: &mut usize, mesh: &BakedMesh) -> result::Result<(JsonValue, usize), SaveMeshError> {
+    let vertexes = &mesh.mesh.positions;
+    let normals = &mesh.mesh.normals;
+    let uvs = &mesh.mesh.texture_coordinates;
+    let lightmap_uvs = &mesh.mesh.lightmap_coordinates;
+    let indices = &mesh.mesh.indices;
     let min_vertex = [
         float_min(vertexes.iter().map(|i| i.x)),
         float_min(vertexes.iter().map(|i| i.y)),
@@ -48,12 +249,209 @@ pub fn save_mesh(
         float_max(vertexes.iter().map(|i| i.y)),
         float_max(vertexes.iter().map(|i| i.z)),
     ];
+    let colors = mesh.colors.as_deref().unwrap_or(&[]);
 
-    let mut image_bytes: Vec<u8> = Vec::new();
-    texture.write_to(&mut Cursor::new(&mut image_bytes), image::ImageOutputFormat::Png)?;
+    let normal_view = push_buffer_view(document, byte_offset, 4 * 3 * normals.len(), Some(4 * 3), Some(ARRAY_BUFFER))?;
+    let position_view = push_buffer_view(document, byte_offset, 4 * 3 * vertexes.len(), Some(4 * 3), Some(ARRAY_BUFFER))?;
+    let uv_view = push_buffer_view(document, byte_offset, 4 * 2 * uvs.len(), Some(4 * 2), Some(ARRAY_BUFFER))?;
+    let lightmap_uv_view = push_buffer_view(document, byte_offset, 4 * 2 * lightmap_uvs.len(), Some(4 * 2), Some(ARRAY_BUFFER))?;
+    let index_view = push_buffer_view(document, byte_offset, 4 * indices.len(), None, Some(ELEMENT_ARRAY_BUFFER))?;
+
+    let mut attributes = object!{
+        "NORMAL"=>push_accessor(document, object!{
+            "bufferView"=>normal_view,
+            "componentType"=> 5126_u32, // Float
+            "count"=> normals.len(),
+            "type"=> "VEC3"
+        })?,
+        "POSITION"=>push_accessor(document, object!{
+            "bufferView"=>position_view,
+            "componentType"=> 5126_u32, // Float
+            "count"=> vertexes.len(),
+            "type"=> "VEC3",
+            "min"=>array![min_vertex[0], min_vertex[1], min_vertex[2]],
+            "max"=>array![max_vertex[0], max_vertex[1], max_vertex[2]],
+        })?,
+        "TEXCOORD_0"=>push_accessor(document, object!{
+            "bufferView"=>uv_view,
+            "componentType"=> 5126_u32, // Float
+            "count"=> uvs.len(),
+            "type"=> "VEC2"
+        })?,
+        "TEXCOORD_1"=>push_accessor(document, object!{
+            "bufferView"=>lightmap_uv_view,
+            "componentType"=> 5126_u32, // Float
+            "count"=> lightmap_uvs.len(),
+            "type"=> "VEC2"
+        })?,
+    };
+    let indices_accessor = push_accessor(document, object!{
+        "bufferView"=>index_view,
+        "componentType"=> 5125_u32, // Unsigned Int
+        "count"=> indices.len(),
+        "type"=> "SCALAR"
+    })?;
+
+    if !colors.is_empty() {
+        let color_view = push_buffer_view(document, byte_offset, 4 * 3 * colors.len(), Some(4 * 3), Some(ARRAY_BUFFER))?;
+        attributes["COLOR_0"] = push_accessor(document, object!{
+            "bufferView"=>color_view,
+            "componentType"=> 5126_u32, // Float
+            "count"=> colors.len(),
+            "type"=> "VEC3"
+        })?.into();
+    }
+    Ok((attributes, indices_accessor))
+}
+
+const NEAREST: u32 = 9728;
+const NEAREST_MIPMAP_LINEAR: u32 = 9986;
+
+/// Adds an image, returning its index.
+fn push_image(document: &mut JsonValue, mut image: JsonValue) -> result::Result<usize, SaveMeshError> {
+    let image_index = document["images"].len();
+    image["name"] = format!("texture{}", image_index).into();
+    document["images"].push(image)?;
+    Ok(image_index)
+}
 
+/// Adds a texture showing `image`, returning its index.
+fn push_texture(document: &mut JsonValue, image: JsonValue) -> result::Result<usize, SaveMeshError> {
+    let image_index = push_image(document, image)?;
+    document["textures"].push(object!{
+        "source"=>image_index,
+        "sampler"=>0
+    })?;
+    Ok(document["textures"].len() - 1)
+}
+
+/// Adds a texture for the next of the [`embedded_images`], whose data is filled in once the
+/// layout of the buffer is known.
+///
+/// With the URI of its KTX2 file the texture also references that through
+/// `KHR_texture_basisu`, keeping the PNG for viewers without it, and samples the mip levels
+/// of the KTX2 file. The sampler blends between levels but takes the nearest pixel in each,
+/// so a face is still never blurred into its neighbours.
+fn push_embedded_texture(document: &mut JsonValue, embedded_images: &mut Vec<usize>, ktx2: Option<String>) -> result::Result<usize, SaveMeshError> {
+    embedded_images.push(document["images"].len());
+    let texture = push_texture(document, object!{
+        "mimeType"=>"image/png"
+    })?;
+    if let Some(uri) = ktx2 {
+        let image = push_image(document, object!{
+            "mimeType"=>"image/ktx2",
+            "uri"=>uri
+        })?;
+        document["textures"][texture]["extensions"]["KHR_texture_basisu"] = object!{
+            "source"=>image
+        };
+        document["textures"][texture]["sampler"] = sampler(document, NEAREST, NEAREST_MIPMAP_LINEAR)?.into();
+        use_extension(document, "KHR_texture_basisu")?;
+    }
+    Ok(texture)
+}
+
+/// The index of the sampler with these filters, adding it when needed.
+fn sampler(document: &mut JsonValue, mag_filter: u32, min_filter: u32) -> result::Result<usize, SaveMeshError> {
+    if let Some(index) = document["samplers"].members().position(|sampler| sampler["magFilter"] == mag_filter && sampler["minFilter"] == min_filter) {
+        return Ok(index);
+    }
+    document["samplers"].push(object!{
+        "magFilter"=>mag_filter,
+        "minFilter"=>min_filter
+    })?;
+    Ok(document["samplers"].len() - 1)
+}
+
+/// Lists an extension in `extensionsUsed`, unless it already is.
+fn use_extension(document: &mut JsonValue, name: &str) -> result::Result<(), SaveMeshError> {
+    if document["extensionsUsed"].is_null() {
+        document["extensionsUsed"] = array![];
+    }
+    if !document["extensionsUsed"].members().any(|used| used == name) {
+        document["extensionsUsed"].push(name)?;
+    }
+    Ok(())
+}
+
+/// The textures of a material.
+struct MaterialTextures {
+    /// The base colour, sampled with `TEXCOORD_0`.
+    base: usize,
+    /// The light without the base colour, sampled with `TEXCOORD_1`.
+    light: Option<usize>,
+    /// The emitted light sampled with `TEXCOORD_1`, and its strength.
+    emissive: Option<(usize, f32)>,
+    /// The ambient occlusion sampled with `TEXCOORD_1`.
+    occlusion: Option<usize>,
+}
+
+/// Adds a material using `textures`, returning its index.
+///
+/// glTF has no lightmap slot, so the light texture is listed as `lightmapTexture` in the
+/// `extras` of the material for engines to pick up.
+fn push_material(document: &mut JsonValue, textures: &MaterialTextures, unlit: bool) -> result::Result<usize, SaveMeshError> {
+    let mut material = object!{
+        "pbrMetallicRoughness" => object!{
+            "baseColorTexture" => object!{
+                "index" => textures.base,
+                "texCoord" => 0
+            }
+        }
+    };
+    if let Some(light) = textures.light {
+        material["extras"]["lightmapTexture"] = object!{
+            "index" => light,
+            "texCoord" => 1
+        };
+    }
+    if let Some((emissive_texture, strength)) = textures.emissive {
+        material["emissiveTexture"] = object!{
+            "index" => emissive_texture,
+            "texCoord" => 1
+        };
+        // The factor is limited to 1, anything stronger needs the extension
+        material["emissiveFactor"] = array![strength.min(1.), strength.min(1.), strength.min(1.)];
+        if strength > 1. {
+            material["extensions"]["KHR_materials_emissive_strength"] = object!{
+                "emissiveStrength" => strength
+            };
+            use_extension(document, "KHR_materials_emissive_strength")?;
+        }
+    }
+    if let Some(occlusion) = textures.occlusion {
+        material["occlusionTexture"] = object!{
+            "index" => occlusion,
+            "texCoord" => 1
+        };
+    }
+    if unlit {
+        // Viewers without the extension fall back to the closest to unlit a PBR material gets
+        material["pbrMetallicRoughness"]["metallicFactor"] = 0.into();
+        material["pbrMetallicRoughness"]["roughnessFactor"] = 1.into();
+        material["extensions"]["KHR_materials_unlit"] = object!{};
+        use_extension(document, "KHR_materials_unlit")?;
+    }
+    document["materials"].push(material)?;
+    Ok(document["materials"].len() - 1)
+}
 
-    let gltf_json_part = object! {
+/// The glTF JSON describing a scene with a node and mesh for every entry of `nodes`.
+///
+/// The binary buffer holds the [`vertex_buffer`] of every node followed by the embedded
+/// textures when they are stored there, see [`scene_buffer`]. Meshes with the same external
+/// texture share it, and their material too when they have no textures of their own.
+///
+/// Textures are sampled without filtering, as every pixel is the light of a single face which
+/// filtering would blur into its neighbours. Meshes with [`BakedMesh::mipmaps`] whose node
+/// knows its KTX2 files reference them for their per face mip levels, see
+/// [`push_embedded_texture`].
+fn gltf_document(nodes: &[SceneNode], images: ImageStorage) -> result::Result<JsonValue, SaveMeshError> {
+    // Without faces the buffer views would be empty and the bounds of the positions infinite
+    if let Some(node) = nodes.iter().find(|node| node.mesh.mesh.indices.is_empty()) {
+        return Err(SaveMeshError::EmptyMeshError(node.name.to_string()));
+    }
+    let mut document = object! {
         "asset"=> object!{
             "generator": "None",
             "version": "2.0"
@@ -62,194 +460,254 @@ pub fn save_mesh(
         "scenes"=>array![
             object!{
                 "name"=> "Scene0",
-                "nodes" => array![0]
-            }
-        ],
-        "nodes"=>array![
-            object!{
-                "mesh"=>0,
-                "name"=>"curve"
-            }
-        ],
-        "meshes"=> array![
-            object!{
-                "primitives"=> array![
-                    object!{
-                        "attributes"=>object!{
-                            "NORMAL"=> 0,
-                            "POSITION"=>1,
-						    "TEXCOORD_0"=>2
-                        },
-                        "indices"=>3,
-                        "material"=>0
-                    }
-                ],
-            }
-        ],
-        "textures"=>array![
-            object!{
-                "source"=>0,
-                "sampler"=>0
-            }
-        ],
-        "images"=>array![
-            object!{
-                "bufferView"=>4,
-                "mimeType"=>"image/png",
-                "name"=>"texture0"
-            }
-        ],
-        "materials"=>array![
-            object!{
-                "pbrMetallicRoughness" => object!{
-                    "baseColorTexture" => object!{
-                        "index" => 0,
-                        "texCoord" => 0
-                    }
-                }
+                "nodes" => array![]
             }
         ],
+        "nodes"=>array![],
+        "meshes"=>array![],
+        "textures"=>array![],
+        "images"=>array![],
+        "materials"=>array![],
         "samplers"=>array![
             object!{
-                "magFilter"=>9728,
-                "minFilter"=>9728
+                "magFilter"=>NEAREST,
+                "minFilter"=>NEAREST
             }
         ],
-        "accessors"=>array![
-            object!{
-                "bufferView"=>0,
-                "componentType"=> 5126_u32, // Float
-                "count"=> normals.len(),
-                "type"=> "VEC3"
-            },
-            object!{
-                "bufferView"=>1,
-                "componentType"=> 5126_u32, // Float
-                "count"=> vertexes.len(),
-                "type"=> "VEC3",
-                "min"=>array![min_vertex[0], min_vertex[1], min_vertex[2]],
-                "max"=>array![max_vertex[0], max_vertex[1], max_vertex[2]],
-            },
-            object!{
-                "bufferView"=>2,
-                "componentType"=> 5126_u32, // Float
-                "count"=> uvs.len(),
-                "type"=> "VEC2"
+        "accessors"=>array![],
+        "bufferViews"=>array![],
+        "buffers"=>array![
+            object!{}
+        ]
+    };
+
+    let mut byte_offset = 0;
+    let mut shared_textures: HashMap<&str, usize> = HashMap::new();
+    let mut shared_materials: HashMap<(usize, bool), usize> = HashMap::new();
+    let mut embedded_images = Vec::new();
+    for (i, node) in nodes.iter().enumerate() {
+        let (attributes, indices) = push_mesh_accessors(&mut document, &mut byte_offset, node.mesh)?;
+        // The KTX2 files follow the embedded images, which are pushed in the same order below
+        let ktx2 = node.ktx2.filter(|_| node.mesh.mipmaps);
+        let mut ktx2_uris = self::embedded_images(node.mesh).into_iter()
+            .map(|(suffix, _)| ktx2.map(|path| uri_encode_path(&format!("{}{}.ktx2", path, suffix))));
+        let texture = match &node.mesh.texture {
+            MeshTexture::External(uri) => match shared_textures.get(uri.as_str()) {
+                Some(texture) => *texture,
+                None => {
+                    let texture = push_texture(&mut document, object!{
+                        "mimeType"=>"image/png",
+                        "uri"=>uri.as_str()
+                    })?;
+                    shared_textures.insert(uri, texture);
+                    texture
+                }
             },
-            object!{
-                "bufferView"=>3,
-                "componentType"=> 5125_u32, // Unsigned Int
-                "count"=> indices.len(),
-                "type"=> "SCALAR"
+            MeshTexture::Embedded(_) => push_embedded_texture(&mut document, &mut embedded_images, ktx2_uris.next().flatten())?,
+        };
+        let light = match &node.mesh.light {
+            Some(_) => Some(push_embedded_texture(&mut document, &mut embedded_images, ktx2_uris.next().flatten())?),
+            None => None,
+        };
+        let emissive = match &node.mesh.emissive {
+            Some(emissive) => Some((push_embedded_texture(&mut document, &mut embedded_images, ktx2_uris.next().flatten())?, emissive.strength)),
+            None => None,
+        };
+        let occlusion = match &node.mesh.occlusion {
+            Some(_) => Some(push_embedded_texture(&mut document, &mut embedded_images, ktx2_uris.next().flatten())?),
+            None => None,
+        };
+        let textures = MaterialTextures { base: texture, light, emissive, occlusion };
+        let material = if light.is_none() && emissive.is_none() && occlusion.is_none() {
+            match shared_materials.get(&(texture, node.mesh.unlit)) {
+                Some(material) => *material,
+                None => {
+                    let material = push_material(&mut document, &textures, node.mesh.unlit)?;
+                    shared_materials.insert((texture, node.mesh.unlit), material);
+                    material
+                }
             }
-        ],
-        "bufferViews"=> array![
-            object!{
-                "buffer"=>0,
-                "byteLength"=>4 *3 * normals.len(),
-                "byteOffset"=>0,
-            },
-            object!{
-                "buffer"=>0,
-                "byteOffset"=>4 * 3 * normals.len(),
-                "byteLength"=>4 * 3 * vertexes.len(),
-            },
+        } else {
+            push_material(&mut document, &textures, node.mesh.unlit)?
+        };
 
-            object!{
-                "buffer"=>0,
-                "byteOffset"=>4 * 3 * normals.len() + 4 * 3 * vertexes.len(),
-                "byteLength"=>4 * 2 * uvs.len(),
-            },
-            object!{
-                "buffer"=>0,
-                "byteOffset"=>4 * 3 * normals.len() + 4 * 3 * vertexes.len() + 4 * 2 * uvs.len(),
-                "byteLength"=>4 * indices.len(),
-            },
-            // Texture
-            object!{
-                "buffer"=>0,
-                "byteLength"=>image_bytes.len(),
-                "byteOffset"=>4 * 3 * normals.len() + 4 * 3 * vertexes.len() + 4 * 2 * uvs.len() + 4 * indices.len()
+        let primitive = object!{
+            "attributes"=>attributes,
+            "indices"=>indices,
+            "material"=>material
+        };
+        document["meshes"].push(object!{
+            "name"=>node.name,
+            "primitives"=>array![primitive],
+        })?;
+
+        let mut json_node = object!{
+            "mesh"=>i,
+            "name"=>node.name
+        };
+        let transform = node.transform;
+        let identity = Transform::default();
+        if transform.translation != identity.translation {
+            json_node["translation"] = transform.translation[..].into();
+        }
+        if transform.rotation != identity.rotation {
+            json_node["rotation"] = transform.rotation[..].into();
+        }
+        if transform.scale != identity.scale {
+            json_node["scale"] = transform.scale[..].into();
+        }
+        document["nodes"].push(json_node)?;
+        document["scenes"][0]["nodes"].push(i)?;
+    }
+
+    for (i, image) in embedded_images.into_iter().enumerate() {
+        match images {
+            ImageStorage::Buffer(lengths) => {
+                document["images"][image]["bufferView"] = push_buffer_view(&mut document, &mut byte_offset, lengths[i], None, None)?.into();
             }
-        ],
-        "buffers"=>array![
-            object!{
-                "byteLength"=>4 * 3 * normals.len() + 4 * 3 * vertexes.len() + 4 * 2 * uvs.len() + 4 * indices.len() + image_bytes.len()
-            },
-        ]
-    };
+            ImageStorage::Uri(uris) => {
+                document["images"][image]["uri"] = uris[i].as_str().into();
+            }
+        }
+    }
+    document["buffers"][0]["byteLength"] = byte_offset.into();
+    Ok(document)
+}
 
-    fs::create_dir_all("cache")?;
-    let mut jsfile = File::create(format!("cache/{:}.json", filename)).unwrap();
-    jsfile.write_all(
-        json::stringify_pretty(gltf_json_part.clone(), 2).as_bytes()
-    )?;
+/// The lengths of the images for [`ImageStorage::Buffer`].
+fn image_lengths(images: &[Vec<u8>]) -> Vec<usize> {
+    images.iter().map(Vec::len).collect()
+}
 
-    let mut data = json::stringify(gltf_json_part);
-    while data.len() % 4 != 0 {
-        data += " "
-    };
+/// Writes a mesh as glTF JSON, referencing the binary buffer written to `bin` as `{name}.bin`,
+/// an embedded texture as `{name}.png`, which can be written with [`texture_png`], and the
+/// light, emissive and occlusion textures as `{name}_light.png`, `{name}_emissive.png` and
+/// `{name}_occlusion.png`.
+pub fn write_gltf<J: Write, B: Write>(mesh: &BakedMesh, name: &str, json: &mut J, bin: &mut B) -> result::Result<(), SaveMeshError> {
+    write_gltf_node(&single_node(mesh, DEFAULT_NODE_NAME, Transform::default(), Some(name)), name, json, bin)
+}
 
+/// Writes a mesh like [`write_gltf`], in the given node.
+fn write_gltf_node<J: Write, B: Write>(nodes: &[SceneNode; 1], name: &str, json: &mut J, bin: &mut B) -> result::Result<(), SaveMeshError> {
+    let image_uris: Vec<String> = embedded_images(nodes[0].mesh).into_iter().map(|(suffix, _)| uri_encode(&format!("{}{}.png", name, suffix))).collect();
+    let mut document = gltf_document(nodes, ImageStorage::Uri(&image_uris))?;
+    document["buffers"][0]["uri"] = uri_encode(&format!("{}.bin", name)).into();
 
-    let buffer_normals: Vec<u8> = normals
-        .iter()
-        .map(|x| [x.x.to_le_bytes(), x.y.to_le_bytes(), x.z.to_le_bytes()])
-        .flatten()
-        .flatten()
-        .collect();
-    let buffer_positions: Vec<u8> = vertexes.iter().map(
-        |x| [x.x.to_le_bytes(), x.y.to_le_bytes(), x.z.to_le_bytes()]).flatten().flatten().collect();
-    let buffer_uvs: Vec<u8> = uvs.iter().map(|x| [x.x.to_le_bytes(), x.y.to_le_bytes()]).flatten().flatten().collect();
-    let buffer_indices: Vec<u8> = indices.iter().map(|x| (*x as u32).to_le_bytes()).flatten().collect();
-
-    let mut file = File::create(format!("cache/{:}.glb", filename)).unwrap();
-    file.write_all("glTF".as_bytes())?;
-    file.write_all(&2_u32.to_le_bytes())?;
-    file.write_all(
-        &(
-            (
-                pad_length(data.len() +
-                    buffer_normals.len() +
-                    buffer_positions.len() +
-                    buffer_uvs.len() +
-                    buffer_indices.len() +
-                    image_bytes.len()
-                ) +
-                    16 + // Chunk headers
-                    12 // Top header
-            ) as u32
-        ).to_le_bytes()
-    )?;
-
-    file.write_all(&(data.len() as u32).to_le_bytes())?;
-    file.write_all("JSON".as_bytes())?;
-    file.write(data.as_bytes())?;
-
-    file.write_all(&(pad_length(
-        buffer_positions.len() + buffer_normals.len() + buffer_uvs.len() + buffer_indices.len() + image_bytes.len()) as u32
-    ).to_le_bytes())?;
-    file.write_all("BIN".as_bytes())?;
-    file.write(&[0])?;
-    file.write_all(buffer_normals.as_slice())?;
-    file.write_all(buffer_positions.as_slice())?;
-    file.write_all(buffer_uvs.as_slice())?;
-    file.write_all(buffer_indices.as_slice())?;
-    //let cursor_position = file.seek(SeekFrom::Current(0))?;
-    //for i in 0..((4 - cursor_position % 4) % 4) {
-    //    file.write(&[0]);
-    //}
-
-    //file.write_all(&(pad_length(image_bytes.len()) as u32).to_le_bytes())?;
-    //file.write_all("BIN".as_bytes())?;
-    //file.write(&[0])?;
-    file.write_all(image_bytes.as_slice())?;
-
-    let cursor_position = file.seek(SeekFrom::Current(0))?;
-    for i in 0..((4 - cursor_position % 4) % 4) {
-        file.write(&[0])?;
-    }
-
-    let mut img_file = File::create(format!("cache/{}.png", filename))?;
-    img_file.write_all(image_bytes.as_slice())?;
-    return result::Result::Ok(());
+    json.write_all(json::stringify_pretty(document, 2).as_bytes())?;
+    bin.write_all(&scene_buffer(nodes, &[]))?;
+    Ok(())
+}
+
+/// Writes a mesh as a single glTF JSON file, with the binary buffer and an embedded texture
+/// stored in it as a base64 data URI.
+pub fn write_gltf_embedded<W: Write>(mesh: &BakedMesh, writer: &mut W) -> result::Result<(), SaveMeshError> {
+    write_gltf_embedded_scene(&single_node(mesh, DEFAULT_NODE_NAME, Transform::default(), None), writer)
+}
+
+/// Writes several meshes as nodes of one scene in a single glTF JSON file, with everything
+/// stored in a base64 data URI.
+pub fn write_gltf_embedded_scene<W: Write>(nodes: &[SceneNode], writer: &mut W) -> result::Result<(), SaveMeshError> {
+    let images = embedded_pngs(nodes)?;
+    let mut document = gltf_document(nodes, ImageStorage::Buffer(&image_lengths(&images)))?;
+    let buffer = scene_buffer(nodes, &images);
+    document["buffers"][0]["uri"] = format!("data:application/octet-stream;base64,{}", base64(&buffer)).into();
+
+    writer.write_all(json::stringify_pretty(document, 2).as_bytes())?;
+    Ok(())
+}
+
+/// Writes a mesh with a single textured material as a binary glTF.
+///
+/// Per vertex colours are written as `COLOR_0`, which viewers multiply with the texture.
+pub fn write_glb<W: Write>(mesh: &BakedMesh, writer: &mut W) -> result::Result<(), SaveMeshError> {
+    write_glb_scene(&single_node(mesh, DEFAULT_NODE_NAME, Transform::default(), None), writer)
+}
+
+/// Writes several meshes as nodes of one scene in a binary glTF.
+pub fn write_glb_scene<W: Write>(nodes: &[SceneNode], writer: &mut W) -> result::Result<(), SaveMeshError> {
+    let images = embedded_pngs(nodes)?;
+    let document = gltf_document(nodes, ImageStorage::Buffer(&image_lengths(&images)))?;
+    write_glb_chunks(&document, scene_buffer(nodes, &images), writer)
+}
+
+fn write_glb_chunks<W: Write>(document: &JsonValue, mut bin_chunk: Vec<u8>, file: &mut W) -> result::Result<(), SaveMeshError> {
+    // Both chunks have to be padded to 4 bytes, the JSON with spaces and the binary with zeros
+    let mut json_chunk = json::stringify(document.clone()).into_bytes();
+    json_chunk.resize(pad_length(json_chunk.len()), b' ');
+    bin_chunk.resize(pad_length(bin_chunk.len()), 0);
+
+    let total_length = 12 + 8 + json_chunk.len() + 8 + bin_chunk.len();
+
+    file.write_all(&GLB_MAGIC.to_le_bytes())?;
+    file.write_all(&GLB_VERSION.to_le_bytes())?;
+    file.write_all(&(total_length as u32).to_le_bytes())?;
+
+    file.write_all(&(json_chunk.len() as u32).to_le_bytes())?;
+    file.write_all(&GLB_JSON_CHUNK.to_le_bytes())?;
+    file.write_all(&json_chunk)?;
+
+    file.write_all(&(bin_chunk.len() as u32).to_le_bytes())?;
+    file.write_all(&GLB_BIN_CHUNK.to_le_bytes())?;
+    file.write_all(&bin_chunk)?;
+    result::Result::Ok(())
+}
+
+/// Saves a mesh as `{filename}.glb`, along with its embedded textures as `{filename}.png`,
+/// `{filename}_light.png`, `{filename}_emissive.png` and `{filename}_occlusion.png`, in a node
+/// called `name` placed with `transform`.
+pub fn save_mesh(mesh: &BakedMesh, name: &str, transform: Transform, filename: &Path) -> result::Result<(), SaveMeshError> {
+    let files = filename.file_name().unwrap_or_default().to_string_lossy();
+    save_glb_scene(&single_node(mesh, name, transform, Some(&files)), filename)?;
+
+    save_embedded_images(mesh, filename)?;
+    result::Result::Ok(())
+}
+
+/// Saves several meshes as nodes of one scene in `{filename}.glb`.
+pub fn save_glb_scene(nodes: &[SceneNode], filename: &Path) -> result::Result<(), SaveMeshError> {
+    if let Some(directory) = filename.parent() {
+        fs::create_dir_all(directory)?;
+    }
+    let images = embedded_pngs(nodes)?;
+    let document = gltf_document(nodes, ImageStorage::Buffer(&image_lengths(&images)))?;
+    let mut file = File::create(with_suffix(filename, ".glb"))?;
+    write_glb_chunks(&document, scene_buffer(nodes, &images), &mut file)
+}
+
+/// Saves a mesh as `{filename}.gltf` with its binary buffer in `{filename}.bin` and its embedded
+/// textures in `{filename}.png`, `{filename}_light.png`, `{filename}_emissive.png` and
+/// `{filename}_occlusion.png`, referenced by relative URIs, in a node called `name` placed with
+/// `transform`.
+pub fn save_gltf(mesh: &BakedMesh, name: &str, transform: Transform, filename: &Path) -> result::Result<(), SaveMeshError> {
+    if let Some(directory) = filename.parent() {
+        fs::create_dir_all(directory)?;
+    }
+    let files = filename.file_name().unwrap_or_default().to_string_lossy();
+
+    // Written to memory first, so a mesh that can't be written leaves no files behind
+    let (mut json, mut bin) = (Vec::new(), Vec::new());
+    write_gltf_node(&single_node(mesh, name, transform, Some(&files)), &files, &mut json, &mut bin)?;
+    fs::write(with_suffix(filename, ".gltf"), json)?;
+    fs::write(with_suffix(filename, ".bin"), bin)?;
+
+    save_embedded_images(mesh, filename)?;
+    Ok(())
+}
+
+/// Saves a mesh as a single `{filename}.gltf` with everything stored in data URIs, in a node
+/// called `name` placed with `transform`.
+pub fn save_gltf_embedded(mesh: &BakedMesh, name: &str, transform: Transform, filename: &Path) -> result::Result<(), SaveMeshError> {
+    let files = filename.file_name().unwrap_or_default().to_string_lossy();
+    save_gltf_embedded_scene(&single_node(mesh, name, transform, Some(&files)), filename)
+}
+
+/// Saves several meshes as nodes of one scene in a single `{filename}.gltf`.
+pub fn save_gltf_embedded_scene(nodes: &[SceneNode], filename: &Path) -> result::Result<(), SaveMeshError> {
+    if let Some(directory) = filename.parent() {
+        fs::create_dir_all(directory)?;
+    }
+    let mut json = Vec::new();
+    write_gltf_embedded_scene(nodes, &mut json)?;
+    fs::write(with_suffix(filename, ".gltf"), json)?;
+    Ok(())
 }
@@ -0,0 +1,75 @@
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::result;
+use crate::export_gltf::{texture_png, with_suffix, SaveMeshError};
+use crate::mesh::{BakedMesh, MeshTexture};
+
+/// Writes the geometry of a mesh as Wavefront OBJ, using the material `material` from the
+/// library `mtl_name`.
+///
+/// Baked per vertex colours are written after the positions, which most tools read even though
+/// it isn't part of the original format.
+pub fn write_obj<W: Write>(mesh: &BakedMesh, mtl_name: &str, material: &str, writer: &mut W) -> result::Result<(), SaveMeshError> {
+    writeln!(writer, "mtllib {}", mtl_name)?;
+    writeln!(writer, "o {}", material)?;
+
+    for (i, position) in mesh.mesh.positions.iter().enumerate() {
+        match &mesh.colors {
+            Some(colors) => writeln!(writer, "v {} {} {} {} {} {}", position.x, position.y, position.z, colors[i][0], colors[i][1], colors[i][2])?,
+            None => writeln!(writer, "v {} {} {}", position.x, position.y, position.z)?,
+        }
+    }
+    // OBJ puts the origin of the texture in the bottom left, glTF in the top left
+    for uv in &mesh.mesh.texture_coordinates {
+        writeln!(writer, "vt {} {}", uv.x, 1. - uv.y)?;
+    }
+    for normal in &mesh.mesh.normals {
+        writeln!(writer, "vn {} {} {}", normal.x, normal.y, normal.z)?;
+    }
+
+    writeln!(writer, "usemtl {}", material)?;
+    for triangle in mesh.mesh.indices.chunks(3) {
+        // Every vertex has its own position, texture coordinate and normal, so one index serves all three
+        let [a, b, c] = [triangle[0] + 1, triangle[1] + 1, triangle[2] + 1];
+        writeln!(writer, "f {a}/{a}/{a} {b}/{b}/{b} {c}/{c}/{c}")?;
+    }
+    Ok(())
+}
+
+/// Writes a material library with a single material using `texture` as its `map_Kd`.
+pub fn write_mtl<W: Write>(material: &str, texture: &str, writer: &mut W) -> result::Result<(), SaveMeshError> {
+    writeln!(writer, "newmtl {}", material)?;
+    writeln!(writer, "Ka 0 0 0")?;
+    writeln!(writer, "Kd 1 1 1")?;
+    writeln!(writer, "Ks 0 0 0")?;
+    writeln!(writer, "illum 1")?;
+    writeln!(writer, "map_Kd {}", texture)?;
+    Ok(())
+}
+
+/// Saves a mesh as `{filename}.obj` with its material in `{filename}.mtl` and an embedded
+/// texture in `{filename}.png`.
+pub fn save_obj(mesh: &BakedMesh, filename: &Path) -> result::Result<(), SaveMeshError> {
+    if let Some(directory) = filename.parent() {
+        fs::create_dir_all(directory)?;
+    }
+    let name = filename.file_name().unwrap_or_default().to_string_lossy();
+    let texture = match &mesh.texture {
+        MeshTexture::Embedded(_) => format!("{}.png", name),
+        MeshTexture::External(uri) => uri.clone(),
+    };
+
+    let mut obj = BufWriter::new(File::create(with_suffix(filename, ".obj"))?);
+    write_obj(mesh, &format!("{}.mtl", name), &name, &mut obj)?;
+    obj.flush()?;
+
+    let mut mtl = File::create(with_suffix(filename, ".mtl"))?;
+    write_mtl(&name, &texture, &mut mtl)?;
+
+    if let MeshTexture::Embedded(_) = mesh.texture {
+        let mut img_file = File::create(with_suffix(filename, ".png"))?;
+        img_file.write_all(&texture_png(&mesh.texture)?)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,81 @@
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::result;
+use crate::export_gltf::{with_suffix, SaveMeshError};
+use crate::mesh::BakedMesh;
+
+/// How the elements of a PLY file are stored.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PlyEncoding {
+    Ascii,
+    BinaryLittleEndian,
+}
+
+/// The baked colour of a vertex, stored the same way as in the lightmap.
+fn vertex_color(mesh: &BakedMesh, vertex: usize) -> [u8; 3] {
+    mesh.vertex_face(vertex).brightness.map(|x| (x * 256.) as u8)
+}
+
+/// Writes a mesh as PLY, with the light the solver baked into each vertex as its colour.
+pub fn write_ply<W: Write>(mesh: &BakedMesh, encoding: PlyEncoding, writer: &mut W) -> result::Result<(), SaveMeshError> {
+    let positions = &mesh.mesh.positions;
+    let normals = &mesh.mesh.normals;
+
+    writeln!(writer, "ply")?;
+    writeln!(writer, "format {} 1.0", match encoding {
+        PlyEncoding::Ascii => "ascii",
+        PlyEncoding::BinaryLittleEndian => "binary_little_endian",
+    })?;
+    writeln!(writer, "comment baked by voxel_generator")?;
+    writeln!(writer, "element vertex {}", positions.len())?;
+    for property in ["x", "y", "z", "nx", "ny", "nz"] {
+        writeln!(writer, "property float {}", property)?;
+    }
+    for property in ["red", "green", "blue"] {
+        writeln!(writer, "property uchar {}", property)?;
+    }
+    writeln!(writer, "element face {}", mesh.mesh.indices.len() / 3)?;
+    writeln!(writer, "property list uchar uint vertex_indices")?;
+    writeln!(writer, "end_header")?;
+
+    for (i, (position, normal)) in positions.iter().zip(normals).enumerate() {
+        let color = vertex_color(mesh, i);
+        match encoding {
+            PlyEncoding::Ascii => writeln!(
+                writer, "{} {} {} {} {} {} {} {} {}",
+                position.x, position.y, position.z, normal.x, normal.y, normal.z, color[0], color[1], color[2]
+            )?,
+            PlyEncoding::BinaryLittleEndian => {
+                for value in [position.x, position.y, position.z, normal.x, normal.y, normal.z] {
+                    writer.write_all(&value.to_le_bytes())?;
+                }
+                writer.write_all(&color)?;
+            }
+        }
+    }
+
+    for triangle in mesh.mesh.indices.chunks(3) {
+        match encoding {
+            PlyEncoding::Ascii => writeln!(writer, "3 {} {} {}", triangle[0], triangle[1], triangle[2])?,
+            PlyEncoding::BinaryLittleEndian => {
+                writer.write_all(&[3])?;
+                for index in triangle {
+                    writer.write_all(&(*index as u32).to_le_bytes())?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Saves a mesh as `{filename}.ply`.
+pub fn save_ply(mesh: &BakedMesh, filename: &Path, encoding: PlyEncoding) -> result::Result<(), SaveMeshError> {
+    if let Some(directory) = filename.parent() {
+        fs::create_dir_all(directory)?;
+    }
+    let mut file = BufWriter::new(File::create(with_suffix(filename, ".ply"))?);
+    write_ply(mesh, encoding, &mut file)?;
+    file.flush()?;
+    Ok(())
+}
@@ -1,49 +1,205 @@
-use image::{RgbaImage, Rgb, Rgba};
-use crate::radiosity_color::RadiosityColor;
-
-pub const COLORS: [[u8; 3]; 256] = [
-    [0, 0, 0], [128, 0, 0], [0, 128, 0], [128, 128, 0], [0, 0, 128], [128, 0, 128], [0, 128, 128], [192, 192, 192], [128, 128, 128], [255, 0, 0], [0, 255, 0], [255, 255, 0], [0, 0, 255], [255, 0, 255], [0, 255, 255], [255, 255, 255], [0, 0, 0], [0, 0, 95], [0, 0, 135], [0, 0, 175], [0, 0, 215], [0, 0, 255], [0, 95, 0], [0, 95, 95], [0, 95, 135], [0, 95, 175], [0, 95, 215], [0, 95, 255], [0, 135, 0], [0, 135, 95], [0, 135, 135], [0, 135, 175], [0, 135, 215], [0, 135, 255], [0, 175, 0], [0, 175, 95], [0, 175, 135], [0, 175, 175], [0, 175, 215], [0, 175, 255], [0, 215, 0], [0, 215, 95], [0, 215, 135], [0, 215, 175], [0, 215, 215], [0, 215, 255], [0, 255, 0], [0, 255, 95], [0, 255, 135], [0, 255, 175], [0, 255, 215], [0, 255, 255], [95, 0, 0], [95, 0, 95], [95, 0, 135], [95, 0, 175], [95, 0, 215], [95, 0, 255], [95, 95, 0], [95, 95, 95], [95, 95, 135], [95, 95, 175], [95, 95, 215], [95, 95, 255], [95, 135, 0], [95, 135, 95], [95, 135, 135], [95, 135, 175], [95, 135, 215], [95, 135, 255], [95, 175, 0], [95, 175, 95], [95, 175, 135], [95, 175, 175], [95, 175, 215], [95, 175, 255], [95, 215, 0], [95, 215, 95], [95, 215, 135], [95, 215, 175], [95, 215, 215], [95, 215, 255], [95, 255, 0], [95, 255, 95], [95, 255, 135], [95, 255, 175], [95, 255, 215], [95, 255, 255], [135, 0, 0], [135, 0, 95], [135, 0, 135], [135, 0, 175], [135, 0, 215], [135, 0, 255], [135, 95, 0], [135, 95, 95], [135, 95, 135], [135, 95, 175], [135, 95, 215], [135, 95, 255], [135, 135, 0], [135, 135, 95], [135, 135, 135], [135, 135, 175], [135, 135, 215], [135, 135, 255], [135, 175, 0], [135, 175, 95], [135, 175, 135], [135, 175, 175], [135, 175, 215], [135, 175, 255], [135, 215, 0], [135, 215, 95], [135, 215, 135], [135, 215, 175], [135, 215, 215], [135, 215, 255], [135, 255, 0], [135, 255, 95], [135, 255, 135], [135, 255, 175], [135, 255, 215], [135, 255, 255], [175, 0, 0], [175, 0, 95], [175, 0, 135], [175, 0, 175], [175, 0, 215], [175, 0, 255], [175, 95, 0], [175, 95, 95], [175, 95, 135], [175, 95, 175], [175, 95, 215], [175, 95, 255], [175, 135, 0], [175, 135, 95], [175, 135, 135], [175, 135, 175], [175, 135, 215], [175, 135, 255], [175, 175, 0], [175, 175, 95], [175, 175, 135], [175, 175, 175], [175, 175, 215], [175, 175, 255], [175, 215, 0], [175, 215, 95], [175, 215, 135], [175, 215, 175], [175, 215, 215], [175, 215, 255], [175, 255, 0], [175, 255, 95], [175, 255, 135], [175, 255, 175], [175, 255, 215], [175, 255, 255], [215, 0, 0], [215, 0, 95], [215, 0, 135], [215, 0, 175], [215, 0, 215], [215, 0, 255], [215, 95, 0], [215, 95, 95], [215, 95, 135], [215, 95, 175], [215, 95, 215], [215, 95, 255], [215, 135, 0], [215, 135, 95], [215, 135, 135], [215, 135, 175], [215, 135, 215], [215, 135, 255], [215, 175, 0], [215, 175, 95], [215, 175, 135], [215, 175, 175], [215, 175, 215], [215, 175, 255], [215, 215, 0], [215, 215, 95], [215, 215, 135], [215, 215, 175], [215, 215, 215], [215, 215, 255], [215, 255, 0], [215, 255, 95], [215, 255, 135], [215, 255, 175], [215, 255, 215], [215, 255, 255], [255, 0, 0], [255, 0, 95], [255, 0, 135], [255, 0, 175], [255, 0, 215], [255, 0, 255], [255, 95, 0], [255, 95, 95], [255, 95, 135], [255, 95, 175], [255, 95, 215], [255, 95, 255], [255, 135, 0], [255, 135, 95], [255, 135, 135], [255, 135, 175], [255, 135, 215], [255, 135, 255], [255, 175, 0], [255, 175, 95], [255, 175, 135], [255, 175, 175], [255, 175, 215], [255, 175, 255], [255, 215, 0], [255, 215, 95], [255, 215, 135], [255, 215, 175], [255, 215, 215], [255, 215, 255], [255, 255, 0], [255, 255, 95], [255, 255, 135], [255, 255, 175], [255, 255, 215], [255, 255, 255], [8, 8, 8], [18, 18, 18], [28, 28, 28], [38, 38, 38], [48, 48, 48], [58, 58, 58], [68, 68, 68], [78, 78, 78], [88, 88, 88], [98, 98, 98], [108, 108, 108], [118, 118, 118], [128, 128, 128], [138, 138, 138], [148, 148, 148], [158, 158, 158], [168, 168, 168], [178, 178, 178], [188, 188, 188], [198, 198, 198], [208, 208, 208], [218, 218, 218], [228, 228, 228], [238, 238, 238]
-];
-
-fn closest_color(color: &Rgba<u8>) -> u8 {
-    let mut min_distance = 256 * 3;
-    let mut min_index = 0;
-    for (index, compare_color) in COLORS.iter().enumerate() {
-        let distance = (compare_color[0] as i32 - color[0] as i32).abs() + (compare_color[1] as i32 - color[1] as i32).abs() + (compare_color[2] as i32 - color[2] as i32).abs();
-        if distance < min_distance {
-            min_index = index;
-            min_distance = distance;
-        }
-    }
-    return min_index as u8;
+use image::RgbaImage;
+use crate::radiosity_color::{RadiosityColor, VoxelGrid};
+use crate::vector::Vec2;
+
+/// Which pixel column of which layer a column of the voxel grid is copied from.
+#[derive(Copy, Clone, Debug)]
+pub struct LayerSample {
+    pub layer: usize,
+    pub column: usize,
+    /// Number of voxels the layer is shifted upwards by, the part pushed out of the grid is cut off.
+    pub rise: usize,
+}
+
+/// The shape of a segment built from a set of layer images.
+///
+/// The layers are cross-sections of one edge of a hallway, with the wall in the last column.
+/// `Straight` and the two curves are edge pieces that use the columns as is. The other shapes
+/// mirror the cross-section around the middle of the grid so they have a wall on both sides.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SegmentShape {
+    Straight,
+    LeftCurve,
+    RightCurve,
+    /// A straight hallway along the X axis.
+    Corridor,
+    /// A hallway along the X axis with a branch leaving towards positive Z.
+    TJunction,
+    /// Two hallways crossing each other.
+    Crossing,
+    /// A hallway along the X axis that is closed off at the positive X side.
+    DeadEnd,
+    /// A hallway along the X axis rising one voxel every two voxels.
+    Ramp,
+    /// A hallway along the X axis rising two voxels every four voxels.
+    Stairs,
+    /// A hallway running diagonally from the origin to the opposite corner.
+    Diagonal,
 }
 
+/// Mirrors a coordinate around the middle of a grid of `size` voxels, so both halves use the
+/// wall side of a layer.
+fn fold(coordinate: usize, size: usize) -> usize {
+    coordinate.max(size - 1 - coordinate)
+}
 
+fn along_x(x: usize, z: usize, size: usize) -> LayerSample {
+    LayerSample { layer: x, column: fold(z, size), rise: 0 }
+}
 
-fn curve_segment(layers: &[RgbaImage; 16], cmp: fn(x: usize, z: usize)->bool, brightness: f32) -> [[[RadiosityColor; 16]; 16]; 16] {
-    let mut grid = [[[RadiosityColor {color: [0, 0, 0, 255].into(), emission: 0.}; 16]; 16]; 16];
-    for x in 0..16 {
-        for y in 0..16 {
-            for z in 0..16 {
-                let color = if cmp(x, z) {layers[x].get_pixel(z as u32, layers[x].height() - 1 - y as u32)} else {
-                    layers[z].get_pixel(x as u32, layers[x].height() - 1 - y as u32)
+fn along_z(x: usize, z: usize, size: usize) -> LayerSample {
+    LayerSample { layer: z, column: fold(x, size), rise: 0 }
+}
+
+/// Joins two hallways, keeping whichever one is more open at this column.
+fn open(a: LayerSample, b: LayerSample) -> LayerSample {
+    if a.column <= b.column { a } else { b }
+}
+
+/// Joins two hallways, keeping whichever one is more closed at this column.
+fn closed(a: LayerSample, b: LayerSample) -> LayerSample {
+    if a.column >= b.column { a } else { b }
+}
+
+impl SegmentShape {
+    /// Which layer column the grid column at `x`, `z` of a grid of `size` voxels is copied from.
+    pub fn sample(&self, x: usize, z: usize, size: usize) -> LayerSample {
+        let half = size / 2;
+        match self {
+            SegmentShape::Straight => LayerSample { layer: x, column: z, rise: 0 },
+            SegmentShape::LeftCurve => if x < z {
+                LayerSample { layer: x, column: z, rise: 0 }
+            } else {
+                LayerSample { layer: z, column: x, rise: 0 }
+            },
+            SegmentShape::RightCurve => if x > z {
+                LayerSample { layer: x, column: z, rise: 0 }
+            } else {
+                LayerSample { layer: z, column: x, rise: 0 }
+            },
+            SegmentShape::Corridor => along_x(x, z, size),
+            SegmentShape::TJunction => if z < half {
+                along_x(x, z, size)
+            } else {
+                open(along_x(x, z, size), along_z(x, z, size))
+            },
+            SegmentShape::Crossing => open(along_x(x, z, size), along_z(x, z, size)),
+            SegmentShape::DeadEnd => if x < half {
+                along_x(x, z, size)
+            } else {
+                closed(along_x(x, z, size), along_z(x, z, size))
+            },
+            SegmentShape::Ramp => LayerSample { rise: x / 2, ..along_x(x, z, size) },
+            SegmentShape::Stairs => LayerSample { rise: x / 4 * 2, ..along_x(x, z, size) },
+            SegmentShape::Diagonal => LayerSample {
+                layer: (x + z) / 2,
+                column: half + x.abs_diff(z) / 2,
+                rise: 0,
+            },
+        }
+    }
+}
+
+/// Fills a grid by copying each column from the layer pixel column chosen by `sample`.
+fn fill_grid<const SIZE: usize>(layers: &[RgbaImage; 16], sample: impl Fn(usize, usize) -> Option<LayerSample>, brightness: f32) -> VoxelGrid<SIZE> {
+    let mut grid = [[[RadiosityColor {color: [0, 0, 0, 0].into(), emission: 0.}; SIZE]; SIZE]; SIZE];
+    for (x, plane) in grid.iter_mut().enumerate() {
+        for (y, row) in plane.iter_mut().enumerate() {
+            for (z, voxel) in row.iter_mut().enumerate() {
+                let sample = match sample(x, z) {
+                    Some(sample) => sample,
+                    None => continue,
                 };
-                grid[x][y][z].color = *color;
-                grid[x][y][z].emission = if color.0 == [255, 255, 255, 255] { brightness } else {0. };
+                let layer = &layers[sample.layer];
+                if y < sample.rise || y - sample.rise >= layer.height() as usize || sample.column >= layer.width() as usize {
+                    continue;
+                }
+                let color = layer.get_pixel(sample.column as u32, layer.height() - 1 - (y - sample.rise) as u32);
+                voxel.color = *color;
+                voxel.emission = if color.0 == [255, 255, 255, 255] { brightness } else {0. };
             }
         }
     };
     grid
 }
 
-pub fn straight_segment(layers: &[RgbaImage; 16], brightness: f32) -> [[[RadiosityColor; 16]; 16]; 16] {
-    return curve_segment(layers, |x, z| true, brightness)
+/// Builds a grid of up to 16 voxels on each side from 16 layers, with voxels of pure white
+/// giving off `brightness`.
+pub fn segment<const SIZE: usize>(layers: &[RgbaImage; 16], shape: SegmentShape, brightness: f32) -> VoxelGrid<SIZE> {
+    fill_grid(layers, |x, z| Some(shape.sample(x, z, SIZE)), brightness)
+}
+
+/// A path on the XZ plane in voxel coordinates.
+#[derive(Clone, Debug)]
+pub enum SweepPath {
+    Polyline(Vec<Vec2>),
+    /// A Catmull-Rom spline passing through every point.
+    Spline(Vec<Vec2>),
+}
+
+const SPLINE_STEPS: usize = 8;
+
+fn catmull_rom(p0: Vec2, p1: Vec2, p2: Vec2, p3: Vec2, t: f32) -> Vec2 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    (p1 * 2. + (p2 - p0) * t + (p0 * 2. - p1 * 5. + p2 * 4. - p3) * t2 + (p1 * 3. - p0 - p2 * 3. + p3) * t3) * 0.5
 }
 
-pub fn left_curve_segment(layers: &[RgbaImage; 16], brightness: f32) -> [[[RadiosityColor; 16]; 16]; 16] {
-    return curve_segment(layers, |x, z| x < z, brightness)
+impl SweepPath {
+    /// The points of the path, with splines sampled at a fixed number of points per span.
+    pub fn to_polyline(&self) -> Vec<Vec2> {
+        match self {
+            SweepPath::Polyline(points) => points.clone(),
+            SweepPath::Spline(points) => {
+                if points.len() < 3 {
+                    return points.clone();
+                }
+                let mut polyline = Vec::new();
+                for i in 0..points.len() - 1 {
+                    // The end points are repeated so the curve reaches them
+                    let p0 = points[i.saturating_sub(1)];
+                    let p3 = points[(i + 2).min(points.len() - 1)];
+                    for step in 0..SPLINE_STEPS {
+                        polyline.push(catmull_rom(p0, points[i], points[i + 1], p3, step as f32 / SPLINE_STEPS as f32));
+                    }
+                }
+                polyline.push(points[points.len() - 1]);
+                polyline
+            }
+        }
+    }
 }
 
-pub fn right_curve_segment(layers: &[RgbaImage; 16], brightness: f32) -> [[[RadiosityColor; 16]; 16]; 16] {
-    return curve_segment(layers, |x, z| x > z, brightness)
+/// Sweeps the layers along `path`, using them as cross-sections mirrored around the path like
+/// [`SegmentShape::Corridor`]. The layers are stretched over the length of the path, so the
+/// first and last layer end up at its ends.
+pub fn sweep_segment<const SIZE: usize>(layers: &[RgbaImage; 16], path: &SweepPath, brightness: f32) -> VoxelGrid<SIZE> {
+    let polyline = path.to_polyline();
+    let length: f32 = polyline.windows(2).map(|line| line[0].distance_squared(&line[1]).sqrt()).sum();
+
+    fill_grid(layers, |x, z| {
+        let position = Vec2 { x: x as f32 + 0.5, y: z as f32 + 0.5 };
+        let mut closest: Option<(f32, f32)> = None;
+        let mut start = 0.;
+        for line in polyline.windows(2) {
+            let direction = line[1] - line[0];
+            let line_length_squared = direction.dot(&direction);
+            let t = if line_length_squared == 0. {
+                0.
+            } else {
+                ((position - line[0]).dot(&direction) / line_length_squared).clamp(0., 1.)
+            };
+            let distance_squared = position.distance_squared(&(line[0] + direction * t));
+            if closest.is_none_or(|(closest_distance, _)| distance_squared < closest_distance) {
+                closest = Some((distance_squared, start + t * line_length_squared.sqrt()));
+            }
+            start += line_length_squared.sqrt();
+        }
+
+        let (distance_squared, along) = closest?;
+        // Matches fold(), where the two voxels next to the center line use the middle column
+        let column = ((distance_squared.sqrt() + (SIZE / 2) as f32 - 0.5) as usize).max(SIZE / 2);
+        let layer = if length == 0. { 0 } else { ((along / length * layers.len() as f32) as usize).min(layers.len() - 1) };
+        Some(LayerSample { layer, column, rise: 0 })
+    }, brightness)
 }
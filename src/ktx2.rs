@@ -0,0 +1,217 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::result;
+use image::{Rgba, RgbaImage};
+use crate::export_gltf::{embedded_images, with_suffix, SaveMeshError};
+use crate::mesh::BakedMesh;
+
+const IDENTIFIER: [u8; 12] = [0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A];
+const VK_FORMAT_R8G8B8A8_SRGB: u32 = 43;
+const VK_FORMAT_BC1_RGB_SRGB_BLOCK: u32 = 132;
+const KHR_DF_MODEL_RGBSDA: u8 = 1;
+const KHR_DF_MODEL_BC1A: u8 = 128;
+const KHR_DF_PRIMARIES_BT709: u8 = 1;
+const KHR_DF_TRANSFER_SRGB: u8 = 2;
+const KHR_DF_CHANNEL_ALPHA: u8 = 15;
+const KHR_DF_SAMPLE_DATATYPE_LINEAR: u8 = 0x10;
+
+/// Bit offset, bit length, channel and upper value of a sample in a data format descriptor.
+type Sample = (u16, u8, u8, u32);
+
+/// The payload of a KTX2 file.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Ktx2Format {
+    /// Uncompressed 8 bit sRGB with alpha.
+    Rgba8,
+    /// BC1 compressed sRGB without alpha, 8 bytes for every block of 4x4 pixels.
+    Bc1,
+}
+
+impl Ktx2Format {
+    pub fn from_name(name: &str) -> Option<Ktx2Format> {
+        match name {
+            "rgba8" => Some(Ktx2Format::Rgba8),
+            "bc1" => Some(Ktx2Format::Bc1),
+            _ => None,
+        }
+    }
+
+    fn vk_format(&self) -> u32 {
+        match self {
+            Ktx2Format::Rgba8 => VK_FORMAT_R8G8B8A8_SRGB,
+            Ktx2Format::Bc1 => VK_FORMAT_BC1_RGB_SRGB_BLOCK,
+        }
+    }
+
+    /// Size in bytes of the smallest unit the data of a level is made of.
+    fn block_size(&self) -> usize {
+        match self {
+            Ktx2Format::Rgba8 => 4,
+            Ktx2Format::Bc1 => 8,
+        }
+    }
+
+    fn encode(&self, image: &RgbaImage) -> Vec<u8> {
+        match self {
+            Ktx2Format::Rgba8 => image.as_raw().clone(),
+            Ktx2Format::Bc1 => bc1(image),
+        }
+    }
+
+    /// The basic data format descriptor block, which tells readers how to interpret the data.
+    fn data_format_descriptor(&self) -> Vec<u8> {
+        let (model, block, samples): (u8, [u8; 4], &[Sample]) = match self {
+            Ktx2Format::Rgba8 => (KHR_DF_MODEL_RGBSDA, [0, 0, 0, 0], &[
+                (0, 8, 0, 255),
+                (8, 8, 1, 255),
+                (16, 8, 2, 255),
+                // Alpha is never sRGB encoded
+                (24, 8, KHR_DF_CHANNEL_ALPHA | KHR_DF_SAMPLE_DATATYPE_LINEAR, 255),
+            ]),
+            Ktx2Format::Bc1 => (KHR_DF_MODEL_BC1A, [3, 3, 0, 0], &[(0, 64, 0, u32::MAX)]),
+        };
+        let block_size = 24 + 16 * samples.len();
+
+        let mut descriptor = Vec::new();
+        descriptor.extend(((4 + block_size) as u32).to_le_bytes());
+        descriptor.extend(0_u32.to_le_bytes()); // Khronos vendor, basic descriptor type
+        descriptor.extend(2_u16.to_le_bytes());
+        descriptor.extend((block_size as u16).to_le_bytes());
+        descriptor.extend([model, KHR_DF_PRIMARIES_BT709, KHR_DF_TRANSFER_SRGB, 0]);
+        descriptor.extend(block);
+        descriptor.extend([self.block_size() as u8, 0, 0, 0, 0, 0, 0, 0]);
+        for (offset, length, channel, upper) in samples {
+            descriptor.extend(offset.to_le_bytes());
+            descriptor.extend([length - 1, *channel, 0, 0, 0, 0]);
+            descriptor.extend(0_u32.to_le_bytes());
+            descriptor.extend(upper.to_le_bytes());
+        }
+        descriptor
+    }
+}
+
+fn to_565(color: [f32; 3]) -> u16 {
+    let r = (color[0] * 31. / 255.).round() as u16;
+    let g = (color[1] * 63. / 255.).round() as u16;
+    let b = (color[2] * 31. / 255.).round() as u16;
+    r << 11 | g << 5 | b
+}
+
+fn from_565(color: u16) -> [f32; 3] {
+    [
+        ((color >> 11) & 31) as f32 * 255. / 31.,
+        ((color >> 5) & 63) as f32 * 255. / 63.,
+        (color & 31) as f32 * 255. / 31.,
+    ]
+}
+
+fn distance_squared(a: [f32; 3], b: [f32; 3]) -> f32 {
+    (0..3).map(|i| (a[i] - b[i]) * (a[i] - b[i])).sum()
+}
+
+/// Compresses a block of 16 pixels, using the ends of its bounding box as the endpoints.
+fn bc1_block(pixels: &[Rgba<u8>; 16]) -> [u8; 8] {
+    let colors = pixels.map(|pixel| [pixel[0] as f32, pixel[1] as f32, pixel[2] as f32]);
+    let min = colors.iter().fold([255_f32; 3], |min, color| std::array::from_fn(|i| min[i].min(color[i])));
+    let max = colors.iter().fold([0_f32; 3], |max, color| std::array::from_fn(|i| max[i].max(color[i])));
+
+    let (mut color0, mut color1) = (to_565(max), to_565(min));
+    if color0 < color1 {
+        std::mem::swap(&mut color0, &mut color1);
+    }
+
+    let mut indices = 0_u32;
+    if color0 != color1 {
+        // The first endpoint being larger selects the mode with two interpolated colours
+        let (end0, end1) = (from_565(color0), from_565(color1));
+        let palette: [[f32; 3]; 4] = [
+            end0,
+            end1,
+            std::array::from_fn(|i| (2. * end0[i] + end1[i]) / 3.),
+            std::array::from_fn(|i| (end0[i] + 2. * end1[i]) / 3.),
+        ];
+        for (i, color) in colors.iter().enumerate() {
+            let closest = (0..4).min_by(|a, b| distance_squared(palette[*a], *color).total_cmp(&distance_squared(palette[*b], *color))).unwrap_or(0);
+            indices |= (closest as u32) << (2 * i);
+        }
+    }
+
+    let mut block = [0; 8];
+    block[0..2].copy_from_slice(&color0.to_le_bytes());
+    block[2..4].copy_from_slice(&color1.to_le_bytes());
+    block[4..8].copy_from_slice(&indices.to_le_bytes());
+    block
+}
+
+/// Compresses an image to BC1, repeating the edge pixels to fill partial blocks.
+fn bc1(image: &RgbaImage) -> Vec<u8> {
+    let mut data = Vec::new();
+    for block_y in 0..image.height().div_ceil(4) {
+        for block_x in 0..image.width().div_ceil(4) {
+            let pixels = std::array::from_fn(|i| {
+                let x = (block_x * 4 + i as u32 % 4).min(image.width() - 1);
+                let y = (block_y * 4 + i as u32 / 4).min(image.height() - 1);
+                *image.get_pixel(x, y)
+            });
+            data.extend(bc1_block(&pixels));
+        }
+    }
+    data
+}
+
+/// Writes a texture as KTX2, with `levels` being its mip levels from the largest to the smallest.
+pub fn write_ktx2<W: Write>(levels: &[RgbaImage], format: Ktx2Format, writer: &mut W) -> result::Result<(), SaveMeshError> {
+    let data: Vec<Vec<u8>> = levels.iter().map(|level| format.encode(level)).collect();
+    let descriptor = format.data_format_descriptor();
+
+    let header_length = IDENTIFIER.len() + 9 * 4 + 4 * 4 + 2 * 8 + levels.len() * 3 * 8;
+    let descriptor_offset = header_length;
+    let alignment = format.block_size().max(4);
+
+    // The smallest level comes first in the file, while the level index starts at the largest
+    let mut level_offsets = vec![0; levels.len()];
+    let mut offset = descriptor_offset + descriptor.len();
+    for level in (0..levels.len()).rev() {
+        offset = offset.div_ceil(alignment) * alignment;
+        level_offsets[level] = offset;
+        offset += data[level].len();
+    }
+
+    let width = levels.first().map_or(0, |level| level.width());
+    let height = levels.first().map_or(0, |level| level.height());
+    writer.write_all(&IDENTIFIER)?;
+    for value in [format.vk_format(), 1, width, height, 0, 0, 1, levels.len() as u32, 0] {
+        writer.write_all(&value.to_le_bytes())?;
+    }
+    for value in [descriptor_offset as u32, descriptor.len() as u32, 0, 0] {
+        writer.write_all(&value.to_le_bytes())?;
+    }
+    writer.write_all(&[0; 16])?; // No supercompression data
+    for (level, level_data) in data.iter().enumerate() {
+        for value in [level_offsets[level], level_data.len(), level_data.len()] {
+            writer.write_all(&(value as u64).to_le_bytes())?;
+        }
+    }
+    writer.write_all(&descriptor)?;
+
+    let mut written = descriptor_offset + descriptor.len();
+    for level in (0..levels.len()).rev() {
+        writer.write_all(&vec![0; level_offsets[level] - written])?;
+        writer.write_all(&data[level])?;
+        written = level_offsets[level] + data[level].len();
+    }
+    Ok(())
+}
+
+/// Saves the textures stored with a mesh next to it as `{filename}{suffix}.ktx2`, with the same
+/// suffixes as the PNG files written by [`crate::save_mesh`], and their mip levels when the
+/// mesh has [`BakedMesh::mipmaps`].
+pub fn save_ktx2(mesh: &BakedMesh, filename: &Path, format: Ktx2Format) -> result::Result<(), SaveMeshError> {
+    for (suffix, image) in embedded_images(mesh) {
+        let mut file = BufWriter::new(File::create(with_suffix(filename, &format!("{}.ktx2", suffix)))?);
+        write_ktx2(&mesh.mip_levels(image), format, &mut file)?;
+        file.flush()?;
+    }
+    Ok(())
+}
@@ -0,0 +1,71 @@
+//! Builds voxel tiles from layer images, bakes radiosity lighting into them and exports the
+//! result as glTF.
+//!
+//! A tile goes through four steps:
+//!
+//! 1. Build a [`VoxelGrid`] from 16 cross-section images with [`segment`] or [`sweep_segment`].
+//! 2. Turn it into a [`Mesh`] with [`voxel_to_mesh`], optionally quantizing colours to a
+//!    [`Palette`].
+//! 3. Bake the lighting with [`bake_mesh`], which runs the solver in [`radiosity`] using
+//!    [`SolverOptions`] and returns a [`BakedMesh`].
+//! 4. Export it with [`write_glb`] to any writer, or with [`save_mesh`] to a path.
+//!
+//! ```no_run
+//! use image::io::Reader as ImageReader;
+//! use voxel_generator::{bake_mesh, segment, SegmentShape, SolverOptions, VoxelGrid, voxel_to_mesh, write_glb};
+//!
+//! let edge = ImageReader::open("media/hallway_edge.png").unwrap().decode().unwrap().into_rgba8();
+//! let layers = std::array::from_fn(|_| edge.clone());
+//! let voxels: VoxelGrid<16> = segment(&layers, SegmentShape::Straight, 0.4);
+//! let baked = bake_mesh(voxel_to_mesh(&voxels, None), &SolverOptions::default());
+//!
+//! let mut glb = Vec::new();
+//! write_glb(&baked, &mut glb).unwrap();
+//! ```
+//!
+//! Whole batches of tiles can be described in a JSON file, see [`scene::Scene`].
+
+/// Writing meshes as glTF.
+pub mod export_gltf;
+/// Writing meshes as Wavefront OBJ.
+pub mod export_obj;
+/// Writing meshes as PLY.
+pub mod export_ply;
+/// Building voxel grids from layer images.
+pub mod image_to_grid;
+/// Writing textures as KTX2.
+pub mod ktx2;
+/// The in memory mesh types.
+pub mod mesh;
+/// Quantizing colours to a fixed set.
+pub mod palette;
+/// Quick images of voxel grids.
+pub mod preview;
+/// The radiosity solver that bakes lighting into faces.
+pub mod radiosity;
+/// A path tracer to check the solver against.
+pub mod reference;
+/// The voxel type.
+pub mod radiosity_color;
+/// Batches of tiles described in JSON.
+pub mod scene;
+/// Voxel grids with known lighting, for checking the solver.
+pub mod test_scenes;
+/// Structural checks of exported glTF files.
+pub mod validate;
+/// Small vector types used for geometry.
+pub mod vector;
+/// Turning voxel grids into meshes.
+pub mod voxel;
+
+pub use export_gltf::{save_glb_scene, save_gltf, save_gltf_embedded, save_gltf_embedded_scene, save_mesh, SaveMeshError, SceneNode, Transform, write_glb, write_glb_scene, write_gltf, write_gltf_embedded, write_gltf_embedded_scene};
+pub use export_obj::{save_obj, write_mtl, write_obj};
+pub use export_ply::{PlyEncoding, save_ply, write_ply};
+pub use image_to_grid::{LayerSample, segment, SegmentShape, sweep_segment, SweepPath};
+pub use ktx2::{Ktx2Format, save_ktx2, write_ktx2};
+pub use mesh::{BakedMesh, Mesh, MeshTexture};
+pub use palette::Palette;
+pub use radiosity::{AmbientOcclusion, Light, Sky, SolverOptions, Sun};
+pub use radiosity_color::{RadiosityColor, VoxelGrid};
+pub use scene::{load_scene, run_scene, Scene, SceneError};
+pub use voxel::{bake_mesh, voxel_to_mesh};
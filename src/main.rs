@@ -1,87 +1,141 @@
-use image::{Rgb, RgbImage};
-use crate::voxel::voxel_to_mesh;
-use image::io::Reader as ImageReader;
-use crate::image_to_grid::{COLORS, left_curve_segment, right_curve_segment, straight_segment};
+use std::{env, fs};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use image::RgbaImage;
+use voxel_generator::Palette;
+use voxel_generator::preview::{contact_sheet, render, top_down, turntable};
+use voxel_generator::reference::{Comparison, TraceOptions};
+use voxel_generator::scene::{bake_tile, load_palette, load_scene, run_scene, Scene, SceneError, tile_voxels};
+use crate::cli::{Command, Options, run_cli, USAGE};
 
-mod export_gltf;
-mod vector;
-mod voxel;
-mod image_to_grid;
-mod radiosity;
-mod radiosity_color;
+mod cli;
 
-fn main() {
-    let mut texture = RgbImage::new(16, 16);
-    for (index, color) in COLORS.iter().enumerate() {
-        texture.put_pixel(
-            (index / 16) as u32,
-            (index % 16) as u32,
-            Rgb(*color),
-        )
+/// Applies the command line options on top of the ones from the scene file.
+fn apply_options(scene: &mut Scene, options: &Options) -> Result<(), SceneError> {
+    if let Some(format) = options.format {
+        scene.format = format;
     }
-    texture.save("media/colormap.png").unwrap();
-
-    let image = ImageReader::open("media/hallway_edge.png").unwrap().decode().unwrap().into_rgba8();
-    let end_image = ImageReader::open("media/hallway_edge_end.png").unwrap().decode().unwrap().into_rgba8();
-    let layers = [
-        end_image.clone(),
-        image.clone(),
-        image.clone(),
-        image.clone(),
-        image.clone(),
-        image.clone(),
-        image.clone(),
-        image.clone(),
-        image.clone(),
-        image.clone(),
-        image.clone(),
-        image.clone(),
-        image.clone(),
-        image.clone(),
-        image.clone(),
-        end_image
-    ];
-
-    let room_image = ImageReader::open("media/room_edge.png").unwrap().decode().unwrap().into_rgba8();
-    let room_end_image = ImageReader::open("media/room_edge_end.png").unwrap().decode().unwrap().into_rgba8();
-
-    let room_layers = [
-        room_end_image.clone(),
-        room_image.clone(),
-        room_image.clone(),
-        room_image.clone(),
-        room_image.clone(),
-        room_image.clone(),
-        room_image.clone(),
-        room_image.clone(),
-        room_image.clone(),
-        room_image.clone(),
-        room_image.clone(),
-        room_image.clone(),
-        room_image.clone(),
-        room_image.clone(),
-        room_image.clone(),
-        room_end_image
-    ];
-
-    let hallway_brightness = 0.4;
-    let room_brightness = 2.0;
+    if let Some(palette) = &options.palette {
+        scene.palette = Some(load_palette(palette, Path::new(""))?);
+    }
+    if let (Some(output), Some(combine)) = (&options.output, &scene.combine) {
+        scene.combine = combine.file_name().map(|name| output.join(name));
+    }
+    if options.ktx2.is_some() {
+        scene.ktx2 = options.ktx2;
+    }
+    if options.unlit {
+        scene.unlit = true;
+    }
+    if options.mipmaps {
+        scene.mipmaps = true;
+    }
+    if let Some(combine) = &options.combine {
+        scene.combine = Some(combine.clone());
+    }
+    if let Some(sampling) = options.sampling {
+        scene.sampling = sampling;
+    }
+    for tile in scene.tiles.iter_mut() {
+        if let Some(output) = &options.output {
+            tile.output = output.join(tile.output.file_name().unwrap_or(tile.name.as_ref()));
+        }
+        if let Some(iterations) = options.iterations {
+            tile.solver.iterations = iterations;
+        }
+        if let Some(subdivisions) = options.subdivisions {
+            tile.solver.subdivisions = subdivisions;
+        }
+    }
+    Ok(())
+}
 
-    voxel_to_mesh(straight_segment(&layers, hallway_brightness
-    ), "hallway".to_string()).unwrap();
-    voxel_to_mesh(
-        left_curve_segment(&layers, hallway_brightness),
-        "hallway_curve_left".to_string()).unwrap();
-    voxel_to_mesh(
-        right_curve_segment(&layers, hallway_brightness),
-        "hallway_curve_right".to_string()).unwrap();
+fn run(command: Command, options: Options) -> Result<(), SceneError> {
+    match command {
+        Command::Help => println!("{}", USAGE),
+        Command::Palette => {
+            let palette = match &options.palette {
+                Some(palette) => load_palette(palette, Path::new(""))?,
+                None => Palette::default(),
+            };
+            let path = options.output.unwrap_or_else(|| PathBuf::from("media/colormap.png"));
+            if let Some(directory) = path.parent() {
+                fs::create_dir_all(directory)?;
+            }
+            palette.to_image().save(path)?;
+        }
+        Command::Bake(path) => {
+            let mut scene = load_scene(&path)?;
+            apply_options(&mut scene, &options)?;
+            run_scene(&scene)?;
+        }
+        Command::Mesh(path) => {
+            let mut scene = load_scene(&path)?;
+            apply_options(&mut scene, &options)?;
+            for tile in scene.tiles.iter_mut() {
+                tile.solver.lighting = false;
+            }
+            run_scene(&scene)?;
+        }
+        Command::Preview(path) => {
+            let mut scene = load_scene(&path)?;
+            apply_options(&mut scene, &options)?;
+            for tile in &scene.tiles {
+                let image = top_down(&tile_voxels(&scene, tile), 8);
+                if let Some(directory) = tile.output.parent() {
+                    fs::create_dir_all(directory)?;
+                }
+                image.save(format!("{}_preview.png", tile.output.to_string_lossy()))?;
+            }
+        }
+        Command::Render(path) => {
+            let mut scene = load_scene(&path)?;
+            apply_options(&mut scene, &options)?;
+            for tile in &scene.tiles {
+                let image = render(&bake_tile(&scene, tile), &scene.camera, scene.sampling);
+                if let Some(directory) = tile.output.parent() {
+                    fs::create_dir_all(directory)?;
+                }
+                image.save(format!("{}_render.png", tile.output.to_string_lossy()))?;
+            }
+        }
+        Command::Sheet(path) => {
+            let mut scene = load_scene(&path)?;
+            apply_options(&mut scene, &options)?;
+            let cameras = turntable(&scene.camera, options.angles.unwrap_or(4).max(1) as usize);
+            let rows: Vec<(&str, Vec<RgbaImage>)> = scene.tiles.iter().map(|tile| {
+                let mesh = bake_tile(&scene, tile);
+                (tile.name.as_str(), cameras.iter().map(|camera| render(&mesh, camera, scene.sampling)).collect())
+            }).collect();
+            // The sheet goes next to the tiles, or in the output directory when there are none
+            let directory = match scene.tiles.first() {
+                Some(tile) => tile.output.parent().unwrap_or(Path::new("")).to_path_buf(),
+                None => options.output.clone().unwrap_or_default(),
+            };
+            fs::create_dir_all(&directory)?;
+            contact_sheet(&rows).save(directory.join("contact_sheet.png"))?;
+        }
+        Command::Compare(path) => {
+            let mut scene = load_scene(&path)?;
+            apply_options(&mut scene, &options)?;
+            for tile in &scene.tiles {
+                let trace_options = TraceOptions {
+                    samples: options.samples.unwrap_or(TraceOptions::default().samples),
+                    bounces: tile.solver.iterations,
+                    ..TraceOptions::default()
+                };
+                let report = Comparison::new(&tile_voxels(&scene, tile), &tile.solver, &trace_options).report(10);
+                println!("{}\n{}", tile.name, report);
+                if let Some(directory) = tile.output.parent() {
+                    fs::create_dir_all(directory)?;
+                }
+                fs::write(format!("{}_comparison.txt", tile.output.to_string_lossy()), report)?;
+            }
+        }
+    }
+    Ok(())
+}
 
-    voxel_to_mesh(straight_segment(&room_layers, room_brightness
-    ), "room".to_string()).unwrap();
-    voxel_to_mesh(
-        left_curve_segment(&room_layers, room_brightness),
-        "room_curve_left".to_string()).unwrap();
-    voxel_to_mesh(
-        right_curve_segment(&room_layers, room_brightness),
-        "room_curve_right".to_string()).unwrap();
+fn main() -> ExitCode {
+    ExitCode::from(run_cli(env::args().skip(1), run))
 }
@@ -0,0 +1,84 @@
+use image::{DynamicImage, RgbaImage};
+use crate::palette::Palette;
+use crate::radiosity::{corner_sub_face, Face, MirrorPlanes};
+use crate::vector::{Vec2, Vec3};
+use crate::voxel::lightmap_mip_levels;
+
+/// Where the base colour texture of a mesh comes from.
+pub enum MeshTexture {
+    /// An image stored with the mesh, such as a baked lightmap.
+    Embedded(DynamicImage),
+    /// An image referenced by a URI relative to the exported file, so several meshes can share it.
+    External(String),
+}
+
+/// The light given off by the emitting faces of a mesh.
+pub struct Emissive {
+    /// Colour of the light at each point, sampled with the lightmap coordinates and scaled so
+    /// the strongest emitter is at full brightness.
+    pub texture: DynamicImage,
+    /// Emission of the strongest emitter, which the texture is multiplied with.
+    pub strength: f32,
+}
+
+/// The geometry of a voxel grid, before lighting is baked into it.
+#[derive(Clone)]
+pub struct Mesh {
+    pub positions: Vec<Vec3>,
+    pub normals: Vec<Vec3>,
+    /// Either the position of each vertex in the lightmap, or in the colormap of `palette`.
+    pub texture_coordinates: Vec<Vec2>,
+    /// The position of each vertex in the lightmap, which every face has a cell of its own in.
+    pub lightmap_coordinates: Vec<Vec2>,
+    pub indices: Vec<usize>,
+    /// One face for every four vertices, in the same order.
+    pub faces: Vec<Face>,
+    /// The palette the colours were quantized to.
+    pub palette: Option<Palette>,
+    /// Where the tile is closed off towards the neighbours the solver mirrors it into.
+    pub mirror_planes: MirrorPlanes,
+}
+
+/// A mesh with its lighting baked, ready to be exported.
+pub struct BakedMesh {
+    pub mesh: Mesh,
+    /// Baked light of every vertex, to be multiplied with the palette colour.
+    pub colors: Option<Vec<[f32; 3]>>,
+    pub texture: MeshTexture,
+    /// The light without the colour of the faces, sampled with the lightmap coordinates, for
+    /// meshes that get their colour from a palette.
+    pub light: Option<DynamicImage>,
+    /// The light of the emitting faces, when there are any.
+    pub emissive: Option<Emissive>,
+    /// The ambient occlusion sampled with the lightmap coordinates, when it is baked but not
+    /// multiplied into the light.
+    pub occlusion: Option<DynamicImage>,
+    /// Whether viewers should show the baked light as is instead of lighting the mesh again.
+    pub unlit: bool,
+    /// Whether the KTX2 files of the textures laid out like the lightmap get mip levels, which
+    /// stop at one pixel per face so they don't mix neighbouring faces, and glTF files saved
+    /// to a path sample them from the KTX2 files saved next to it.
+    pub mipmaps: bool,
+    /// The faces after being subdivided and lit by the solver.
+    pub lit_faces: Vec<Face>,
+    /// Number of times the faces were subdivided, see [`corner_sub_face`].
+    pub subdivisions: u8,
+}
+
+impl BakedMesh {
+    /// The mip levels to store for a texture laid out like the lightmap, or only the texture
+    /// itself without [`BakedMesh::mipmaps`].
+    pub fn mip_levels(&self, texture: &DynamicImage) -> Vec<RgbaImage> {
+        if self.mipmaps {
+            lightmap_mip_levels(&texture.to_rgba8(), self.subdivisions)
+        } else {
+            vec![texture.to_rgba8()]
+        }
+    }
+
+    /// The lit sub-face touching a vertex.
+    pub fn vertex_face(&self, vertex: usize) -> &Face {
+        let sub_faces = 1 << (2 * self.subdivisions as usize);
+        &self.lit_faces[vertex / 4 * sub_faces + corner_sub_face(vertex % 4, self.subdivisions)]
+    }
+}
@@ -0,0 +1,92 @@
+use image::{Rgb, Rgba, RgbImage, RgbaImage};
+use crate::vector::Vec2;
+
+/// The default palette, the 256 colours of an xterm.
+pub const COLORS: [[u8; 3]; 256] = [
+    [0, 0, 0], [128, 0, 0], [0, 128, 0], [128, 128, 0], [0, 0, 128], [128, 0, 128], [0, 128, 128], [192, 192, 192], [128, 128, 128], [255, 0, 0], [0, 255, 0], [255, 255, 0], [0, 0, 255], [255, 0, 255], [0, 255, 255], [255, 255, 255], [0, 0, 0], [0, 0, 95], [0, 0, 135], [0, 0, 175], [0, 0, 215], [0, 0, 255], [0, 95, 0], [0, 95, 95], [0, 95, 135], [0, 95, 175], [0, 95, 215], [0, 95, 255], [0, 135, 0], [0, 135, 95], [0, 135, 135], [0, 135, 175], [0, 135, 215], [0, 135, 255], [0, 175, 0], [0, 175, 95], [0, 175, 135], [0, 175, 175], [0, 175, 215], [0, 175, 255], [0, 215, 0], [0, 215, 95], [0, 215, 135], [0, 215, 175], [0, 215, 215], [0, 215, 255], [0, 255, 0], [0, 255, 95], [0, 255, 135], [0, 255, 175], [0, 255, 215], [0, 255, 255], [95, 0, 0], [95, 0, 95], [95, 0, 135], [95, 0, 175], [95, 0, 215], [95, 0, 255], [95, 95, 0], [95, 95, 95], [95, 95, 135], [95, 95, 175], [95, 95, 215], [95, 95, 255], [95, 135, 0], [95, 135, 95], [95, 135, 135], [95, 135, 175], [95, 135, 215], [95, 135, 255], [95, 175, 0], [95, 175, 95], [95, 175, 135], [95, 175, 175], [95, 175, 215], [95, 175, 255], [95, 215, 0], [95, 215, 95], [95, 215, 135], [95, 215, 175], [95, 215, 215], [95, 215, 255], [95, 255, 0], [95, 255, 95], [95, 255, 135], [95, 255, 175], [95, 255, 215], [95, 255, 255], [135, 0, 0], [135, 0, 95], [135, 0, 135], [135, 0, 175], [135, 0, 215], [135, 0, 255], [135, 95, 0], [135, 95, 95], [135, 95, 135], [135, 95, 175], [135, 95, 215], [135, 95, 255], [135, 135, 0], [135, 135, 95], [135, 135, 135], [135, 135, 175], [135, 135, 215], [135, 135, 255], [135, 175, 0], [135, 175, 95], [135, 175, 135], [135, 175, 175], [135, 175, 215], [135, 175, 255], [135, 215, 0], [135, 215, 95], [135, 215, 135], [135, 215, 175], [135, 215, 215], [135, 215, 255], [135, 255, 0], [135, 255, 95], [135, 255, 135], [135, 255, 175], [135, 255, 215], [135, 255, 255], [175, 0, 0], [175, 0, 95], [175, 0, 135], [175, 0, 175], [175, 0, 215], [175, 0, 255], [175, 95, 0], [175, 95, 95], [175, 95, 135], [175, 95, 175], [175, 95, 215], [175, 95, 255], [175, 135, 0], [175, 135, 95], [175, 135, 135], [175, 135, 175], [175, 135, 215], [175, 135, 255], [175, 175, 0], [175, 175, 95], [175, 175, 135], [175, 175, 175], [175, 175, 215], [175, 175, 255], [175, 215, 0], [175, 215, 95], [175, 215, 135], [175, 215, 175], [175, 215, 215], [175, 215, 255], [175, 255, 0], [175, 255, 95], [175, 255, 135], [175, 255, 175], [175, 255, 215], [175, 255, 255], [215, 0, 0], [215, 0, 95], [215, 0, 135], [215, 0, 175], [215, 0, 215], [215, 0, 255], [215, 95, 0], [215, 95, 95], [215, 95, 135], [215, 95, 175], [215, 95, 215], [215, 95, 255], [215, 135, 0], [215, 135, 95], [215, 135, 135], [215, 135, 175], [215, 135, 215], [215, 135, 255], [215, 175, 0], [215, 175, 95], [215, 175, 135], [215, 175, 175], [215, 175, 215], [215, 175, 255], [215, 215, 0], [215, 215, 95], [215, 215, 135], [215, 215, 175], [215, 215, 215], [215, 215, 255], [215, 255, 0], [215, 255, 95], [215, 255, 135], [215, 255, 175], [215, 255, 215], [215, 255, 255], [255, 0, 0], [255, 0, 95], [255, 0, 135], [255, 0, 175], [255, 0, 215], [255, 0, 255], [255, 95, 0], [255, 95, 95], [255, 95, 135], [255, 95, 175], [255, 95, 215], [255, 95, 255], [255, 135, 0], [255, 135, 95], [255, 135, 135], [255, 135, 175], [255, 135, 215], [255, 135, 255], [255, 175, 0], [255, 175, 95], [255, 175, 135], [255, 175, 175], [255, 175, 215], [255, 175, 255], [255, 215, 0], [255, 215, 95], [255, 215, 135], [255, 215, 175], [255, 215, 215], [255, 215, 255], [255, 255, 0], [255, 255, 95], [255, 255, 135], [255, 255, 175], [255, 255, 215], [255, 255, 255], [8, 8, 8], [18, 18, 18], [28, 28, 28], [38, 38, 38], [48, 48, 48], [58, 58, 58], [68, 68, 68], [78, 78, 78], [88, 88, 88], [98, 98, 98], [108, 108, 108], [118, 118, 118], [128, 128, 128], [138, 138, 138], [148, 148, 148], [158, 158, 158], [168, 168, 168], [178, 178, 178], [188, 188, 188], [198, 198, 198], [208, 208, 208], [218, 218, 218], [228, 228, 228], [238, 238, 238]
+];
+
+/// A set of up to 256 colours that voxel colours can be quantized to.
+///
+/// Entries are laid out column-major in the colormap image, so index `i` lives at pixel
+/// `(i / height, i % height)`.
+#[derive(Clone, Debug)]
+pub struct Palette {
+    pub colors: Vec<[u8; 3]>,
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette {
+            colors: COLORS.to_vec(),
+            width: 16,
+            height: 16,
+        }
+    }
+}
+
+impl Palette {
+    /// Reads a palette from a colormap image, such as the one written by [`Palette::to_image`].
+    ///
+    /// Only the first 256 pixels are used since faces reference entries by a `u8` index.
+    pub fn from_image(image: &RgbaImage) -> Palette {
+        let mut colors = Vec::new();
+        for x in 0..image.width() {
+            for y in 0..image.height() {
+                if colors.len() < 256 {
+                    let pixel = image.get_pixel(x, y);
+                    colors.push([pixel[0], pixel[1], pixel[2]]);
+                }
+            }
+        }
+        Palette {
+            colors,
+            width: image.width(),
+            height: image.height(),
+        }
+    }
+
+    /// Draws the palette as a colormap image, one pixel per entry.
+    pub fn to_image(&self) -> RgbImage {
+        let mut texture = RgbImage::new(self.width, self.height);
+        for (index, color) in self.colors.iter().enumerate() {
+            texture.put_pixel(
+                index as u32 / self.height,
+                index as u32 % self.height,
+                Rgb(*color),
+            )
+        }
+        texture
+    }
+
+    /// Index of the entry closest to `color` by the sum of the channel differences.
+    pub fn closest_color(&self, color: &Rgba<u8>) -> u8 {
+        let mut min_distance = 256 * 3;
+        let mut min_index = 0;
+        for (index, compare_color) in self.colors.iter().enumerate() {
+            let distance = (compare_color[0] as i32 - color[0] as i32).abs() + (compare_color[1] as i32 - color[1] as i32).abs() + (compare_color[2] as i32 - color[2] as i32).abs();
+            if distance < min_distance {
+                min_index = index;
+                min_distance = distance;
+            }
+        }
+        min_index as u8
+    }
+
+    /// The index and colour of the closest entry, keeping the alpha of `color`.
+    pub fn quantize(&self, color: &Rgba<u8>) -> (u8, Rgba<u8>) {
+        let index = self.closest_color(color);
+        let entry = self.colors[index as usize];
+        (index, [entry[0], entry[1], entry[2], color[3]].into())
+    }
+
+    /// Texture coordinate of the center of the colormap pixel holding `index`.
+    pub fn texture_coordinate(&self, index: u8) -> Vec2 {
+        Vec2 {
+            x: ((index as u32 / self.height) as f32 + 0.5) / self.width as f32,
+            y: ((index as u32 % self.height) as f32 + 0.5) / self.height as f32,
+        }
+    }
+}
@@ -0,0 +1,353 @@
+use image::{imageops, DynamicImage, Rgba, RgbaImage};
+use crate::mesh::{BakedMesh, MeshTexture};
+use crate::radiosity_color::VoxelGrid;
+use crate::vector::{Vec2, Vec3};
+
+/// Draws the highest voxel of every column as seen from above, darkened the lower it is.
+///
+/// Each voxel becomes a `scale` by `scale` block of pixels, with X to the right and Z down.
+#[allow(clippy::needless_range_loop)]
+pub fn top_down<const SIZE: usize>(voxels: &VoxelGrid<SIZE>, scale: u32) -> RgbaImage {
+    let mut image = RgbaImage::new(SIZE as u32, SIZE as u32);
+    for (x, plane) in voxels.iter().enumerate() {
+        for z in 0..SIZE {
+            if let Some(y) = (0..SIZE).rev().find(|y| plane[*y][z].color[3] != 0) {
+                let color = plane[y][z].color;
+                let shade = 0.5 + 0.5 * (y + 1) as f32 / SIZE as f32;
+                image.put_pixel(x as u32, z as u32, Rgba([
+                    (color[0] as f32 * shade) as u8,
+                    (color[1] as f32 * shade) as u8,
+                    (color[2] as f32 * shade) as u8,
+                    255,
+                ]));
+            }
+        }
+    }
+    imageops::resize(&image, SIZE as u32 * scale, SIZE as u32 * scale, imageops::FilterType::Nearest)
+}
+
+/// Where [`render`] looks at a mesh from, in the coordinates of the mesh.
+#[derive(Copy, Clone, Debug)]
+pub struct Camera {
+    pub position: Vec3,
+    pub target: Vec3,
+    /// Vertical field of view in radians.
+    pub fov: f32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Default for Camera {
+    /// Looks down at the middle of a tile from above one of its corners.
+    fn default() -> Self {
+        Camera {
+            position: Vec3 { x: 1.5, y: 2.1, z: 1.75 },
+            target: Vec3 { x: 0.5, y: 0.25, z: 0.5 },
+            fov: 45_f32.to_radians(),
+            width: 256,
+            height: 256,
+        }
+    }
+}
+
+/// How textures are filtered by [`render`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Sampling {
+    /// The closest pixel, showing every face of the lightmap as it was baked.
+    Nearest,
+    /// A blend of the four closest pixels, like viewers with linear filtering.
+    Bilinear,
+}
+
+impl Sampling {
+    pub fn from_name(name: &str) -> Option<Sampling> {
+        match name {
+            "nearest" => Some(Sampling::Nearest),
+            "bilinear" => Some(Sampling::Bilinear),
+            _ => None,
+        }
+    }
+}
+
+/// Distance from the camera at which triangles are cut off.
+const NEAR: f32 = 0.001;
+
+/// A corner of a triangle, in the space of the camera with Z pointing away from it.
+#[derive(Copy, Clone)]
+struct Vertex {
+    position: Vec3,
+    uv: Vec2,
+    lightmap_uv: Vec2,
+}
+
+impl Vertex {
+    fn lerp(&self, other: &Vertex, t: f32) -> Vertex {
+        Vertex {
+            position: self.position + (other.position - self.position) * t,
+            uv: self.uv + (other.uv - self.uv) * t,
+            lightmap_uv: self.lightmap_uv + (other.lightmap_uv - self.lightmap_uv) * t,
+        }
+    }
+}
+
+/// Cuts off the part of a triangle closer to the camera than [`NEAR`], which leaves up to four corners.
+fn clip_near(triangle: [Vertex; 3]) -> Vec<Vertex> {
+    let mut corners = Vec::new();
+    for i in 0..3 {
+        let (current, next) = (triangle[i], triangle[(i + 1) % 3]);
+        if current.position.z >= NEAR {
+            corners.push(current);
+        }
+        if (current.position.z >= NEAR) != (next.position.z >= NEAR) {
+            corners.push(current.lerp(&next, (NEAR - current.position.z) / (next.position.z - current.position.z)));
+        }
+    }
+    corners
+}
+
+/// The colour of an image at texture coordinates, with 0 to 1 spanning the image.
+fn sample(image: &RgbaImage, uv: Vec2, sampling: Sampling) -> [f32; 3] {
+    let (width, height) = (image.width() as i64, image.height() as i64);
+    let pixel = |x: i64, y: i64| {
+        let pixel = image.get_pixel(x.clamp(0, width - 1) as u32, y.clamp(0, height - 1) as u32);
+        [pixel[0], pixel[1], pixel[2]].map(|x| x as f32 / 255.)
+    };
+    match sampling {
+        Sampling::Nearest => pixel((uv.x * width as f32).floor() as i64, (uv.y * height as f32).floor() as i64),
+        Sampling::Bilinear => {
+            let (x, y) = (uv.x * width as f32 - 0.5, uv.y * height as f32 - 0.5);
+            let (x0, y0) = (x.floor(), y.floor());
+            let (tx, ty) = (x - x0, y - y0);
+            let (x0, y0) = (x0 as i64, y0 as i64);
+            let (a, b, c, d) = (pixel(x0, y0), pixel(x0 + 1, y0), pixel(x0, y0 + 1), pixel(x0 + 1, y0 + 1));
+            std::array::from_fn(|i| {
+                (a[i] * (1. - tx) + b[i] * tx) * (1. - ty) + (c[i] * (1. - tx) + d[i] * tx) * ty
+            })
+        }
+    }
+}
+
+/// Twice the signed area of the triangle `a`, `b`, `p`, positive when `p` lies to one side of
+/// the edge from `a` to `b`.
+fn edge(a: Vec2, b: Vec2, p: Vec2) -> f32 {
+    (b.x - a.x) * (p.y - a.y) - (b.y - a.y) * (p.x - a.x)
+}
+
+/// Draws a baked mesh as seen from `camera`, leaving the background transparent.
+///
+/// Each pixel is the base colour times the light and ambient occlusion textures, when the mesh
+/// has them, so it shows what an unlit viewer would. Meshes using a palette are drawn with the
+/// colormap of their palette.
+pub fn render(mesh: &BakedMesh, camera: &Camera, sampling: Sampling) -> RgbaImage {
+    let base = match (&mesh.texture, &mesh.mesh.palette) {
+        (MeshTexture::Embedded(image), _) => image.to_rgba8(),
+        (MeshTexture::External(_), Some(palette)) => DynamicImage::ImageRgb8(palette.to_image()).to_rgba8(),
+        (MeshTexture::External(_), None) => RgbaImage::from_pixel(1, 1, Rgba([255; 4])),
+    };
+    let lightmaps: Vec<RgbaImage> = [&mesh.light, &mesh.occlusion].into_iter().flatten().map(|image| image.to_rgba8()).collect();
+
+    let forward = (camera.target - camera.position).normalize();
+    // Y is up, unless the camera looks straight along it
+    let up = if forward.y.abs() > 0.999 { Vec3 { x: 0., y: 0., z: -1. } } else { Vec3 { x: 0., y: 1., z: 0. } };
+    let right = forward.cross(&up).normalize();
+    let up = right.cross(&forward);
+    let focal = 1. / (camera.fov * 0.5).tan();
+    let aspect = camera.width as f32 / camera.height as f32;
+    let (width, height) = (camera.width as f32, camera.height as f32);
+
+    let mut image = RgbaImage::new(camera.width, camera.height);
+    let mut depth = vec![f32::INFINITY; (camera.width * camera.height) as usize];
+
+    for triangle in mesh.mesh.indices.chunks_exact(3) {
+        let corners = clip_near([0, 1, 2].map(|i| {
+            let index = triangle[i];
+            let relative = mesh.mesh.positions[index] - camera.position;
+            Vertex {
+                position: Vec3 { x: relative.dot(&right), y: relative.dot(&up), z: relative.dot(&forward) },
+                uv: mesh.mesh.texture_coordinates[index],
+                lightmap_uv: mesh.mesh.lightmap_coordinates[index],
+            }
+        }));
+        if corners.len() < 3 {
+            continue;
+        }
+        let screen: Vec<Vec2> = corners.iter().map(|corner| Vec2 {
+            x: (corner.position.x / corner.position.z * focal / aspect + 1.) * 0.5 * width,
+            y: (1. - corner.position.y / corner.position.z * focal) * 0.5 * height,
+        }).collect();
+
+        // The clipped corners form a convex polygon, drawn as a fan of triangles
+        for i in 1..corners.len() - 1 {
+            let [a, b, c] = [0, i, i + 1];
+            let area = edge(screen[a], screen[b], screen[c]);
+            if area.abs() < f32::EPSILON {
+                continue;
+            }
+            let min_x = screen[a].x.min(screen[b].x).min(screen[c].x).floor().max(0.) as u32;
+            let min_y = screen[a].y.min(screen[b].y).min(screen[c].y).floor().max(0.) as u32;
+            let max_x = (screen[a].x.max(screen[b].x).max(screen[c].x).ceil().max(0.) as u32).min(camera.width);
+            let max_y = (screen[a].y.max(screen[b].y).max(screen[c].y).ceil().max(0.) as u32).min(camera.height);
+
+            for y in min_y..max_y {
+                for x in min_x..max_x {
+                    let point = Vec2 { x: x as f32 + 0.5, y: y as f32 + 0.5 };
+                    let weights = [
+                        edge(screen[b], screen[c], point) / area,
+                        edge(screen[c], screen[a], point) / area,
+                        edge(screen[a], screen[b], point) / area,
+                    ];
+                    if weights.iter().any(|weight| *weight < 0.) {
+                        continue;
+                    }
+
+                    // Interpolating over the depth keeps the textures in perspective
+                    let vertices = [corners[a], corners[b], corners[c]];
+                    let inverse_depth: f32 = (0..3).map(|j| weights[j] / vertices[j].position.z).sum();
+                    let pixel_depth = 1. / inverse_depth;
+                    let index = (y * camera.width + x) as usize;
+                    if pixel_depth >= depth[index] {
+                        continue;
+                    }
+                    depth[index] = pixel_depth;
+
+                    let interpolate = |attribute: fn(&Vertex) -> Vec2| {
+                        (0..3).fold(Vec2 { x: 0., y: 0. }, |sum, j| sum + attribute(&vertices[j]) * (weights[j] / vertices[j].position.z)) * pixel_depth
+                    };
+                    let uv = interpolate(|vertex| vertex.uv);
+                    let lightmap_uv = interpolate(|vertex| vertex.lightmap_uv);
+
+                    let mut color = sample(&base, uv, sampling);
+                    for lightmap in &lightmaps {
+                        let factor = sample(lightmap, lightmap_uv, sampling);
+                        color = std::array::from_fn(|i| color[i] * factor[i]);
+                    }
+                    image.put_pixel(x, y, Rgba([
+                        (color[0] * 255.).round() as u8,
+                        (color[1] * 255.).round() as u8,
+                        (color[2] * 255.).round() as u8,
+                        255,
+                    ]));
+                }
+            }
+        }
+    }
+    image
+}
+
+/// `count` cameras circling the target of `camera` at the same height and distance, starting
+/// with `camera` itself.
+pub fn turntable(camera: &Camera, count: usize) -> Vec<Camera> {
+    let offset = camera.position - camera.target;
+    (0..count).map(|i| {
+        let angle = i as f32 / count as f32 * std::f32::consts::TAU;
+        let (sin, cos) = angle.sin_cos();
+        Camera {
+            position: camera.target + Vec3 { x: offset.x * cos - offset.z * sin, y: offset.y, z: offset.x * sin + offset.z * cos },
+            ..*camera
+        }
+    }).collect()
+}
+
+/// Rows of a 5 by 7 pixel font, with the leftmost pixel in the highest of the 5 bits.
+fn glyph(character: char) -> [u8; 7] {
+    match character.to_ascii_uppercase() {
+        '0' => [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E],
+        '1' => [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        '2' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F],
+        '3' => [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E],
+        '4' => [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02],
+        '5' => [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E],
+        '6' => [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E],
+        '7' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E],
+        '9' => [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C],
+        'A' => [0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'B' => [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E],
+        'C' => [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E],
+        'D' => [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C],
+        'E' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F],
+        'F' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10],
+        'G' => [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F],
+        'H' => [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'I' => [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        'J' => [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C],
+        'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F],
+        'M' => [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'N' => [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+        'O' => [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'P' => [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10],
+        'Q' => [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D],
+        'R' => [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11],
+        'S' => [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E],
+        'T' => [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'V' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04],
+        'W' => [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A],
+        'X' => [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11],
+        'Y' => [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04],
+        'Z' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F],
+        '-' => [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00],
+        '_' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C],
+        ' ' => [0x00; 7],
+        _ => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04],
+    }
+}
+
+/// Size of a pixel of the font on the contact sheet.
+const TEXT_SCALE: u32 = 2;
+/// Space around and between the labels and images of the contact sheet.
+const SHEET_MARGIN: u32 = 8;
+const SHEET_BACKGROUND: Rgba<u8> = Rgba([40, 40, 40, 255]);
+const TEXT_COLOR: Rgba<u8> = Rgba([230, 230, 230, 255]);
+
+/// Writes `text` with its top left corner at `x`, `y`, cutting off what doesn't fit.
+fn draw_text(image: &mut RgbaImage, text: &str, x: u32, y: u32) {
+    for (i, character) in text.chars().enumerate() {
+        let left = x + i as u32 * 6 * TEXT_SCALE;
+        for (row, bits) in glyph(character).iter().enumerate() {
+            for column in 0..5 {
+                if bits & (0x10 >> column) == 0 {
+                    continue;
+                }
+                for dy in 0..TEXT_SCALE {
+                    for dx in 0..TEXT_SCALE {
+                        let (px, py) = (left + column * TEXT_SCALE + dx, y + row as u32 * TEXT_SCALE + dy);
+                        if px < image.width() && py < image.height() {
+                            image.put_pixel(px, py, TEXT_COLOR);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Lays out one labelled row of images for every entry of `rows`, on an opaque background so
+/// the transparent parts of renders read as empty space.
+pub fn contact_sheet(rows: &[(&str, Vec<RgbaImage>)]) -> RgbaImage {
+    let cell_width = rows.iter().flat_map(|(_, images)| images).map(|image| image.width()).max().unwrap_or(0);
+    let cell_height = rows.iter().flat_map(|(_, images)| images).map(|image| image.height()).max().unwrap_or(0);
+    let columns = rows.iter().map(|(_, images)| images.len() as u32).max().unwrap_or(0);
+    let label_width = rows.iter().map(|(label, _)| label.chars().count() as u32 * 6 * TEXT_SCALE).max().unwrap_or(0);
+    let label_height = 7 * TEXT_SCALE + SHEET_MARGIN;
+    let row_height = label_height + cell_height + SHEET_MARGIN;
+
+    let width = (SHEET_MARGIN + columns * (cell_width + SHEET_MARGIN)).max(label_width + 2 * SHEET_MARGIN);
+    let height = SHEET_MARGIN + rows.len() as u32 * row_height;
+    let mut sheet = RgbaImage::from_pixel(width, height, SHEET_BACKGROUND);
+    for (row, (label, images)) in rows.iter().enumerate() {
+        let top = SHEET_MARGIN + row as u32 * row_height;
+        draw_text(&mut sheet, label, SHEET_MARGIN, top);
+        for (column, image) in images.iter().enumerate() {
+            let left = SHEET_MARGIN + column as u32 * (cell_width + SHEET_MARGIN);
+            for (x, y, pixel) in image.enumerate_pixels() {
+                if pixel[3] != 0 {
+                    sheet.put_pixel(left + x, top + label_height + y, *pixel);
+                }
+            }
+        }
+    }
+    sheet
+}
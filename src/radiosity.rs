@@ -1,7 +1,9 @@
+use std::f32::consts::PI;
 use image::{Pixel, Rgb, Rgba, RgbaImage};
-use crate::radiosity_color::RadiosityColor;
-use crate::vector::{Axis, AXISES, Vec2, Vec3};
+use crate::radiosity_color::VoxelGrid;
+use crate::vector::{Axis, Vec2, Vec3};
 
+/// A square surface of the mesh, lit by the solver.
 #[derive(Copy, Clone, Debug)]
 pub struct Face {
     pub corners: [Vec3; 4],
@@ -11,10 +13,12 @@ pub struct Face {
     pub last_iteration_brightness: [f32; 3],
     pub(crate) id: u32,
     pub(crate) color: Rgba<u8>,
+    /// How much light the face gives off, relative to its colour.
+    pub emission: f32,
 }
 
 fn get_subdivisions() -> [[(usize, usize); 4]; 4] {
-    return [
+    [
         [
             (0, 0),
             (0, 1),
@@ -39,7 +43,7 @@ fn get_subdivisions() -> [[(usize, usize); 4]; 4] {
             (2, 3),
             (3, 3)
         ]
-    ];
+    ]
 }
 
 struct FaceRefections {
@@ -66,9 +70,6 @@ impl Iterator for FaceRefections {
             return Option::None
         }
 
-        // if (self.iteration & 4) != 0 {
-        //     face2 = face2.reflect(Axis::Z);
-        // }
         if (self.iteration & 2) != 0 {
             face2 = face2.reflect(Axis::X);
         }
@@ -77,7 +78,50 @@ impl Iterator for FaceRefections {
         }
         self.iteration += 1;
 
-        return Some(face2)
+        Some(face2)
+    }
+}
+
+/// Which cells of the X and Z planes the tile is closed off at, one for every voxel along the
+/// side of the tile.
+///
+/// The solver mirrors the tile across these planes to stand in for its neighbours, so the light
+/// of the mirrored copies can only reach the tile through the cells it leaves open.
+#[derive(Clone, Debug)]
+pub struct MirrorPlanes {
+    size: usize,
+    /// Whether the voxels at X = 0 are filled, by Y and Z.
+    x: Vec<bool>,
+    /// Whether the voxels at Z = 0 are filled, by X and Y.
+    z: Vec<bool>,
+}
+
+impl MirrorPlanes {
+    pub fn new<const SIZE: usize>(voxels: &VoxelGrid<SIZE>) -> MirrorPlanes {
+        let filled = |x: usize, y: usize, z: usize| voxels[x][y][z].color[3] != 0;
+        MirrorPlanes {
+            size: SIZE,
+            x: (0..SIZE * SIZE).map(|i| filled(0, i / SIZE, i % SIZE)).collect(),
+            z: (0..SIZE * SIZE).map(|i| filled(i / SIZE, i % SIZE, 0)).collect(),
+        }
+    }
+
+    /// Whether the way from `position` in the tile to `copy` in a mirrored copy of it crosses
+    /// one of the planes where the tile is closed off.
+    fn blocked(&self, position: Vec3, copy: Vec3) -> bool {
+        let crossing = |axis: Axis| {
+            let (from, to) = (position.get_axis(axis), copy.get_axis(axis));
+            position + (copy - position) * (from / (from - to))
+        };
+        let cell = |value: f32| ((value * self.size as f32).max(0.) as usize).min(self.size - 1);
+        let covered = |cells: &[bool], u: f32, v: f32| cells[cell(u) * self.size + cell(v)];
+        (copy.x < 0. && {
+            let point = crossing(Axis::X);
+            covered(&self.x, point.y, point.z)
+        }) || (copy.z < 0. && {
+            let point = crossing(Axis::Z);
+            covered(&self.z, point.x, point.y)
+        })
     }
 }
 
@@ -92,34 +136,27 @@ impl Face {
                 last_iteration_brightness: self.last_iteration_brightness,
                 id: self.id,
                 color: self.color,
+                emission: self.emission,
             }
         )
     }
 
-    fn center(&self) -> Vec3 {
-        return Vec3 {
+    pub(crate) fn center(&self) -> Vec3 {
+        Vec3 {
             x: (self.corners[0].x + self.corners[1].x + self.corners[2].x + self.corners[3].x) / 4.,
             y: (self.corners[0].y + self.corners[1].y + self.corners[2].y + self.corners[3].y) / 4.,
             z: (self.corners[0].z + self.corners[1].z + self.corners[2].z + self.corners[3].z) / 4.,
-        };
+        }
     }
 
-    fn size(&self) -> Vec3 {
-        return Vec3 {
-            x: self.corners[0].x.max(self.corners[1].x).max(self.corners[2].x).max(self.corners[3].x),
-            y: self.corners[0].y.max(self.corners[1].y).max(self.corners[2].y).max(self.corners[3].y),
-            z: self.corners[0].z.max(self.corners[1].z).max(self.corners[2].z).max(self.corners[3].z),
-        } - Vec3 {
-            x: self.corners[0].x.min(self.corners[1].x).min(self.corners[2].x).min(self.corners[3].x),
-            y: self.corners[0].y.min(self.corners[1].y).min(self.corners[2].y).min(self.corners[3].y),
-            z: self.corners[0].z.min(self.corners[1].z).min(self.corners[2].z).min(self.corners[3].z),
-        };
+    pub(crate) fn area(&self) -> f32 {
+        self.corners[0].distance_squared(&self.corners[1]).sqrt() * self.corners[0].distance_squared(&self.corners[2]).sqrt()
     }
 
     fn distance_squared(&self, other: &Self) -> f32 {
         let c1 = self.center();
         let c2 = other.center();
-        return c1.distance_squared(&c2);
+        c1.distance_squared(&c2)
     }
 
     fn reflect(&self, axis: Axis) -> Face {
@@ -130,78 +167,347 @@ impl Face {
             brightness: self.brightness,
             last_iteration_brightness: self.last_iteration_brightness,
             id: self.id,
-            color: self.color
+            color: self.color,
+            emission: self.emission,
         }
     }
 }
 
-fn test_intersection(p1: Vec3, p2: Vec3, face: &Face) -> bool {
-    let box_center = face.center();
-    let box_size = face.size();
-
-    let line_length = p2 - p2;
-    let min = box_center - box_size * 0.5;
-    let max = box_center + box_size * 0.5;
+/// A light that isn't part of the mesh, in the coordinates of the mesh.
+///
+/// `color` is the light received by a surface facing the light at a distance of 1, or for an
+/// area light the brightness of its surface like the brightness of an emitting face.
+#[derive(Clone, Debug)]
+pub enum Light {
+    /// Shines in every direction from `position`.
+    Point { position: Vec3, color: [f32; 3] },
+    /// Shines from `position` along `direction`, fully inside `inner_angle` and fading out
+    /// towards `outer_angle`, both in radians from the direction.
+    Spot { position: Vec3, direction: Vec3, inner_angle: f32, outer_angle: f32, color: [f32; 3] },
+    /// A rectangle spanned by `edges` from `corner`, shining towards the side `edges[0]`
+    /// crossed with `edges[1]` points to.
+    Area { corner: Vec3, edges: [Vec3; 2], color: [f32; 3] },
+}
 
-    let begin_to_min = min - p1;
-    let begin_to_max = max - p1;
+/// Number of points along each edge an area light is sampled at.
+const AREA_LIGHT_SAMPLES: usize = 4;
 
-    let mut near = -1.0;
-    let mut far = 3.0;
+impl Light {
+    fn reflect(&self, axis: Axis) -> Light {
+        match self {
+            Light::Point { position, color } => Light::Point { position: position.reflect(axis), color: *color },
+            Light::Spot { position, direction, inner_angle, outer_angle, color } => Light::Spot {
+                position: position.reflect(axis),
+                direction: direction.reflect(axis),
+                inner_angle: *inner_angle,
+                outer_angle: *outer_angle,
+                color: *color,
+            },
+            // Mirroring flips the side the rectangle faces, so swap the edges to keep it
+            Light::Area { corner, edges, color } => Light::Area {
+                corner: corner.reflect(axis),
+                edges: [edges[1].reflect(axis), edges[0].reflect(axis)],
+                color: *color,
+            },
+        }
+    }
 
-    for axis in AXISES {
-        if line_length.get_axis(axis) == 0. {
-            if begin_to_min.get_axis(axis) > 0. || begin_to_max.get_axis(axis) < 0. {
-                return false;
+    /// The light falling on `position` from this light, for a surface facing `outside`.
+    ///
+    /// `visible` tells whether a point of the light can be seen from `position`, so that
+    /// anything in between casts a shadow.
+    pub(crate) fn received_at(&self, position: Vec3, outside: Vec3, visible: impl Fn(Vec3) -> bool) -> [f32; 3] {
+        let from = |source: Vec3| {
+            let distance_squared = position.distance_squared(&source);
+            let difference = (position - source).normalize();
+            let cosine = if visible(source) { (difference * -1.).dot(&outside).max(0.) } else { 0. };
+            (difference, distance_squared, cosine)
+        };
+        let factor = match self {
+            Light::Point { position: source, .. } => {
+                let (_, distance_squared, cosine) = from(*source);
+                cosine / distance_squared
             }
-        } else {
-            let t1 = begin_to_min.get_axis(axis) / line_length.get_axis(axis);
-            let t2 = begin_to_max.get_axis(axis) / line_length.get_axis(axis);
+            Light::Spot { position: source, direction, inner_angle, outer_angle, .. } => {
+                let (difference, distance_squared, cosine) = from(*source);
+                let angle = difference.dot(&direction.normalize()).clamp(-1., 1.).acos();
+                let cone = if angle <= *inner_angle {
+                    1.
+                } else {
+                    ((outer_angle - angle) / (outer_angle - inner_angle).max(f32::EPSILON)).clamp(0., 1.)
+                };
+                cone * cosine / distance_squared
+            }
+            Light::Area { corner, edges, .. } => {
+                let normal = edges[0].cross(&edges[1]);
+                let area = normal.dot(&normal).sqrt() / (AREA_LIGHT_SAMPLES * AREA_LIGHT_SAMPLES) as f32;
+                let normal = normal.normalize();
+                let mut factor = 0.;
+                for i in 0..AREA_LIGHT_SAMPLES {
+                    for j in 0..AREA_LIGHT_SAMPLES {
+                        let u = (i as f32 + 0.5) / AREA_LIGHT_SAMPLES as f32;
+                        let v = (j as f32 + 0.5) / AREA_LIGHT_SAMPLES as f32;
+                        let (difference, distance_squared, cosine) = from(*corner + edges[0] * u + edges[1] * v);
+                        factor += cosine * difference.dot(&normal).max(0.) * area / distance_squared;
+                    }
+                }
+                // Spread over the hemisphere like the light of an emitting face in the solver
+                factor / PI
+            }
+        };
+        let color = match self {
+            Light::Point { color, .. } | Light::Spot { color, .. } | Light::Area { color, .. } => color,
+        };
+        color.map(|x| x * factor)
+    }
+}
 
-            let t_min = t1.min(t2);
-            let t_max = t1.max(t2);
+/// Adds the light falling on each face from `lights` to its brightness, so the solver bounces
+/// it around like the light of emitting faces.
+///
+/// The lights are mirrored across the X and Z planes the same way as the faces in the solver,
+/// and `occluders` between a light and a face keep it in the shadow.
+pub fn apply_lights(faces: &mut [Face], occluders: &[Face], lights: &[Light]) {
+    let mirrored: Vec<Light> = lights.iter().flat_map(|light| {
+        let x = light.reflect(Axis::X);
+        [light.reflect(Axis::Z), x.reflect(Axis::Z), x, light.clone()]
+    }).collect();
 
-            if t_min > near {
-                near = t_min
+    for face in faces.iter_mut() {
+        // The normals point into the voxel, the light arrives from the other side
+        let outside = face.normal * -1.;
+        let origin = face.center() + outside * RAY_OFFSET;
+        let visible = |source: Vec3| {
+            let towards = source - origin;
+            let distance = towards.dot(&towards).sqrt();
+            distance <= RAY_OFFSET || !occluded(occluders, origin, towards * (1. / distance), distance - RAY_OFFSET)
+        };
+        for light in &mirrored {
+            let received = light.received_at(face.center(), outside, visible);
+            for (i, received) in received.iter().enumerate() {
+                face.brightness[i] += face.color[i] as f32 / 256. * received;
             }
-            if t_max > far {
-                far = t_max
+        }
+    }
+}
+
+/// Light from a sky dome, blending from `horizon` to `zenith` with the height of the direction
+/// it comes from.
+///
+/// The colours are the light a surface facing up receives with nothing around it from a sky of
+/// that colour everywhere.
+#[derive(Copy, Clone, Debug)]
+pub struct Sky {
+    pub zenith: [f32; 3],
+    pub horizon: [f32; 3],
+}
+
+/// Light from a sun infinitely far away, shining along `direction`.
+///
+/// `color` is the light received by a surface facing the sun.
+#[derive(Copy, Clone, Debug)]
+pub struct Sun {
+    pub direction: Vec3,
+    pub color: [f32; 3],
+}
+
+/// Number of directions the sky is sampled in from every face.
+const SKY_SAMPLES: usize = 64;
+
+/// Offset of the rays from the faces they start at, so they don't hit the face itself.
+const RAY_OFFSET: f32 = 1e-4;
+
+/// Whether a ray from `origin` along the unit vector `direction` hits one of `faces` before
+/// `max_distance`.
+fn occluded(faces: &[Face], origin: Vec3, direction: Vec3, max_distance: f32) -> bool {
+    faces.iter().any(|face| {
+        let denominator = direction.dot(&face.normal);
+        if denominator.abs() < f32::EPSILON {
+            return false;
+        }
+        let distance = (face.corners[0] - origin).dot(&face.normal) / denominator;
+        if distance <= RAY_OFFSET || distance > max_distance {
+            return false;
+        }
+        let hit = origin + direction * distance - face.corners[0];
+        let (edge1, edge2) = (face.corners[1] - face.corners[0], face.corners[2] - face.corners[0]);
+        let u = hit.dot(&edge1) / edge1.dot(&edge1);
+        let v = hit.dot(&edge2) / edge2.dot(&edge2);
+        (0. ..=1.).contains(&u) && (0. ..=1.).contains(&v)
+    })
+}
+
+/// Directions spread over the hemisphere around `normal`, denser towards it like the cosine
+/// falloff of the light received from them.
+fn hemisphere_directions(normal: Vec3) -> impl Iterator<Item=Vec3> {
+    let helper = if normal.x.abs() < 0.9 { Vec3 { x: 1., y: 0., z: 0. } } else { Vec3 { x: 0., y: 1., z: 0. } };
+    let tangent = normal.cross(&helper).normalize();
+    let bitangent = normal.cross(&tangent);
+    let golden_angle = std::f32::consts::PI * (3. - 5_f32.sqrt());
+    (0..SKY_SAMPLES).map(move |i| {
+        let height = (i as f32 + 0.5) / SKY_SAMPLES as f32;
+        let radius = height.sqrt();
+        let angle = i as f32 * golden_angle;
+        tangent * (radius * angle.cos()) + bitangent * (radius * angle.sin()) + normal * (1. - height).sqrt()
+    })
+}
+
+/// Adds the light falling on each face from the sky and the sun to its brightness, leaving out
+/// directions in which `occluders` block the view.
+///
+/// The occluders are used as given, pass them mirrored like the faces in the solver for the
+/// neighbouring tiles to cast shadows as well.
+pub fn apply_environment(faces: &mut [Face], occluders: &[Face], sky: Option<Sky>, sun: Option<Sun>) {
+    for face in faces.iter_mut() {
+        // The normals point into the voxel, the light arrives from the other side
+        let outside = face.normal * -1.;
+        let origin = face.center() + outside * RAY_OFFSET;
+        let mut received = [0.; 3];
+
+        if let Some(sky) = sky {
+            for direction in hemisphere_directions(outside) {
+                if direction.y <= 0. || occluded(occluders, origin, direction, f32::INFINITY) {
+                    // Below the horizon is the ground, which is left to the bounces
+                    continue;
+                }
+                for (i, received) in received.iter_mut().enumerate() {
+                    let color = sky.horizon[i] + (sky.zenith[i] - sky.horizon[i]) * direction.y;
+                    *received += color / SKY_SAMPLES as f32;
+                }
             }
-            if near > far || far < 0. {
-                return false;
+        }
+
+        if let Some(sun) = sun {
+            let towards_sun = sun.direction.normalize() * -1.;
+            let cosine = towards_sun.dot(&outside);
+            if cosine > 0. && !occluded(occluders, origin, towards_sun, f32::INFINITY) {
+                for (i, received) in received.iter_mut().enumerate() {
+                    *received += sun.color[i] * cosine;
+                }
             }
         }
+
+        for (i, received) in received.iter().enumerate() {
+            face.brightness[i] += face.color[i] as f32 / 256. * received;
+        }
     }
-    return (near > 0. && near < 1.) || (far > 0. && far < 1.);
 }
 
-pub fn radiosity_subdivide(faces: &mut Vec<Face>, iterations: u8, subdivisions: u8) -> RgbaImage {
-    let mut faces2 = faces.iter().map(|x| x.subdivide()).flatten().collect();
-    simulate_radiosity(&mut faces2, iterations)
+/// How ambient occlusion is baked, see [`ambient_occlusion`].
+#[derive(Copy, Clone, Debug)]
+pub struct AmbientOcclusion {
+    /// How far away faces still darken a face, in the coordinates of the mesh.
+    pub distance: f32,
+    /// Whether the occlusion is multiplied into the lightmap instead of stored as a texture of
+    /// its own.
+    pub multiply: bool,
+}
+
+/// How much of the hemisphere above each face is open within `distance`, from 0 for fully
+/// enclosed to 1 for nothing around it, weighted like the light received from each direction.
+///
+/// This is much faster than the solver, as rays only need to be tested against `occluders`
+/// for a short distance and there are no bounces.
+pub fn ambient_occlusion(faces: &[Face], occluders: &[Face], distance: f32) -> Vec<f32> {
+    faces.iter().map(|face| {
+        let outside = face.normal * -1.;
+        let origin = face.center() + outside * RAY_OFFSET;
+        // Only faces that could be hit within the distance need to be tested
+        let nearby: Vec<Face> = occluders.iter()
+            .filter(|occluder| occluder.distance_squared(face).sqrt() <= distance + occluder.area().sqrt())
+            .copied()
+            .collect();
+        let open = hemisphere_directions(outside).filter(|direction| !occluded(&nearby, origin, *direction, distance)).count();
+        open as f32 / SKY_SAMPLES as f32
+    }).collect()
+}
+
+#[derive(Clone, Debug)]
+pub struct SolverOptions {
+    /// Number of light bounces to simulate.
+    pub iterations: u8,
+    /// How many times every face is split into four before simulating, each one doubles the
+    /// lightmap resolution.
+    pub subdivisions: u8,
+    /// When false the faces keep their own colour, for checking the geometry without baking.
+    pub lighting: bool,
+    /// Lights shining on the faces besides the emitting ones.
+    pub lights: Vec<Light>,
+    pub sky: Option<Sky>,
+    pub sun: Option<Sun>,
+    /// Bakes ambient occlusion on top of the light, which also works without `lighting`.
+    pub ambient_occlusion: Option<AmbientOcclusion>,
 }
 
-pub fn simulate_radiosity(faces: &mut Vec<Face>, iterations: u8) -> RgbaImage {
-    println!("Face original: {:?}", faces[0]);
-    println!("Face: {:?}", faces[0].subdivide());
-    //let occluder_faces = faces.clone();
-    let mut texture: RgbaImage = RgbaImage::new(128, 128);
-    let size = faces.len();
+impl Default for SolverOptions {
+    fn default() -> Self {
+        SolverOptions {
+            iterations: 4,
+            subdivisions: 1,
+            lighting: true,
+            lights: Vec::new(),
+            sky: None,
+            sun: None,
+            ambient_occlusion: None,
+        }
+    }
+}
+
+/// Index within the sub-faces of one face of the sub-face touching `corner`.
+pub fn corner_sub_face(corner: usize, subdivisions: u8) -> usize {
+    corner * ((1 << (2 * subdivisions as usize)) - 1) / 3
+}
 
+/// The faces with their copies mirrored across the X=0 and Z=0 planes, which is how the
+/// neighbouring tiles look from inside this one.
+pub fn mirrored_faces(faces: &[Face]) -> Vec<Face> {
+    faces.iter().copied().flat_map(FaceRefections::new).collect()
+}
+
+/// Splits every face into four `options.subdivisions` times and runs the simulation on the result.
+///
+/// The `4^subdivisions` sub-faces of `faces[i]` are returned next to each other in order of `i`,
+/// see [`corner_sub_face`] for which one touches each corner of the original face.
+pub fn radiosity_subdivide(faces: &[Face], planes: &MirrorPlanes, options: &SolverOptions) -> Vec<Face> {
+    let mut faces2: Vec<Face> = faces.to_vec();
+    for _ in 0..options.subdivisions {
+        faces2 = faces2.iter().flat_map(|x| x.subdivide()).collect();
+    }
+    if options.lighting {
+        // The same mirrored copies the solver bounces the light between
+        let occluders = mirrored_faces(faces);
+        apply_lights(&mut faces2, &occluders, &options.lights);
+        if options.sky.is_some() || options.sun.is_some() {
+            apply_environment(&mut faces2, &occluders, options.sky, options.sun);
+        }
+        simulate_radiosity(&mut faces2, planes, options.iterations);
+    } else {
+        for face in faces2.iter_mut() {
+            face.brightness = face.color.to_rgb().0.map(|i| i as f32 / 256.);
+        }
+    }
+    faces2
+}
+
+/// Bounces the light given off by the faces around `iterations` times, adding the light each
+/// face reflects to its `brightness`.
+///
+/// The brightness is the light leaving a face in total, spread evenly over the hemisphere in
+/// front of it, so a face receives `1 / pi` of it per unit of solid angle it covers.
+///
+/// The faces are mirrored across the X and Z planes to stand in for the neighbouring tiles,
+/// whose light only gets in where `planes` are open.
+pub fn simulate_radiosity(faces: &mut [Face], planes: &MirrorPlanes, iterations: u8) {
     for face in faces.iter_mut() {
         face.last_iteration_brightness = face.brightness;
     }
-    //let mut faces1 = faces.clone();
-    for i in 0..iterations {
-        println!("Radiosity iteration {}, Faces: {}", i, size);
-        let faces2 = faces.clone();
+    for _ in 0..iterations {
+        let faces2 = faces.to_vec();
         for face in faces.iter_mut() {
-            for i in 0..3 {
-                face.brightness[i] += face.last_iteration_brightness[i]
-            }
             face.last_iteration_brightness = [0.; 3];
         }
 
-        for (face_index, face) in faces.iter_mut().enumerate() {
+        for face in faces.iter_mut() {
             for face2_base in &faces2 {
                 if face.id == face2_base.id {
                     continue;
@@ -214,43 +520,38 @@ pub fn simulate_radiosity(faces: &mut Vec<Face>, iterations: u8) -> RgbaImage {
                     let position2 = face2.center();
                     let difference = (position1 - position2).normalize();
 
-                    // let mut intersects = false;
-                    // for face3 in &occluder_faces {
-                    //     if face.id == face3.id || face2.id == face3.id {
-                    //         continue;
-                    //     }
-                    //     if test_intersection(position1, position2, face3) {
-                    //         intersects = true;
-                    //         break;
-                    //     }
-                    // }
-                    assert!(face.distance_squared(&face2) >= 1. / 4096., "distance equals: {}, face 1 ID: {} {:?}, face 2 ID: {} {:?}", face.distance_squared(&face2), face.id, face.center(), face2.id, face2.center());
+                    // Treating faces as points breaks down once they are closer than their size,
+                    // limit the distance so touching faces can't get an unbounded share of the light
+                    let distance_squared = face.distance_squared(&face2).max(face2.area() / 4.);
 
                     let factor = (difference.dot(&face.normal)).max(0.) * (-difference.dot(&face2.normal)).max(0.);
-                    //if !intersects {
+                    if factor == 0. || planes.blocked(position1, position2) {
+                        continue;
+                    }
                     for i in 0..3 {
                         face.last_iteration_brightness[i] += (face.color[i] as f32 / 256.)
                             * face2.last_iteration_brightness[i]
-                            * (1. / face.distance_squared(&face2)) / 32. / 32.
-                            * factor;
+                            * (1. / distance_squared) * face2.area()
+                            * factor / PI;
                     }
                 }
-                //}
             }
         }
-    }
 
-    for face in faces.iter_mut() {
-        for i in 0..3 {
-            face.brightness[i] += face.last_iteration_brightness[i]
+        // Only the light reflected in this bounce is new, what was there before is already counted
+        for face in faces.iter_mut() {
+            for i in 0..3 {
+                face.brightness[i] += face.last_iteration_brightness[i]
+            }
         }
     }
+}
+
+pub fn lightmap(faces: &[Face], size: u32) -> RgbaImage {
+    let mut texture: RgbaImage = RgbaImage::new(size, size);
 
-    for (index, face) in faces.iter().enumerate() {
+    for face in faces.iter() {
         let brightness = face.brightness;
-        // println!("setting pixel {} {} to value {}", index as u32 / 64,
-        //          index as u32 % 64,
-        //          brightness);
         let mut color = Rgb::to_rgba(&brightness.map(|x| (x * 256.) as u8).into());
         color[3] = 255;
         texture.put_pixel(
@@ -260,5 +561,5 @@ pub fn simulate_radiosity(faces: &mut Vec<Face>, iterations: u8) -> RgbaImage {
         )
     }
 
-    return texture;
+    texture
 }
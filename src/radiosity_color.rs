@@ -1,7 +1,12 @@
 use image::Rgba;
 
+/// A single voxel, with an alpha of 0 marking it as empty.
 #[derive(Copy, Clone, Debug)]
 pub struct RadiosityColor {
     pub color: Rgba<u8>,
+    /// Light given off by the voxel, multiplied with its colour.
     pub emission: f32
 }
+
+/// A cube of voxels indexed as `grid[x][y][z]`, with Y pointing up.
+pub type VoxelGrid<const SIZE: usize> = [[[RadiosityColor; SIZE]; SIZE]; SIZE];
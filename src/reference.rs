@@ -0,0 +1,273 @@
+use std::f32::consts::PI;
+use std::fmt::Write;
+use crate::mesh::Mesh;
+use crate::radiosity::{Face, radiosity_subdivide, SolverOptions};
+use crate::radiosity_color::VoxelGrid;
+use crate::vector::Vec3;
+use crate::voxel::voxel_to_mesh;
+
+/// Settings of the reference path tracer.
+#[derive(Copy, Clone, Debug)]
+pub struct TraceOptions {
+    /// Paths started from every face.
+    pub samples: u32,
+    /// Times the light bounces before reaching a face, like the iterations of the solver.
+    pub bounces: u8,
+    /// Start of the random numbers, the same seed gives the same estimates.
+    pub seed: u64,
+}
+
+impl Default for TraceOptions {
+    fn default() -> Self {
+        TraceOptions {
+            samples: 256,
+            bounces: 4,
+            seed: 1,
+        }
+    }
+}
+
+/// A small random number generator (xorshift64*), so the estimates are reproducible.
+struct Random(u64);
+
+impl Random {
+    fn new(seed: u64) -> Random {
+        // Zero would only ever give zeros, and nearby seeds should still differ
+        Random(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+    }
+
+    /// A number from 0 up to but excluding 1.
+    fn next(&mut self) -> f32 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        (self.0.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 40) as f32 / (1_u64 << 24) as f32
+    }
+}
+
+/// A random direction around `normal`, more likely the closer it is to the normal, like the
+/// light a surface receives from it.
+fn cosine_direction(normal: Vec3, random: &mut Random) -> Vec3 {
+    let helper = if normal.x.abs() < 0.9 { Vec3 { x: 1., y: 0., z: 0. } } else { Vec3 { x: 0., y: 1., z: 0. } };
+    let tangent = normal.cross(&helper).normalize();
+    let bitangent = normal.cross(&tangent);
+    let (height, angle) = (random.next(), random.next() * 2. * PI);
+    let radius = height.sqrt();
+    tangent * (radius * angle.cos()) + bitangent * (radius * angle.sin()) + normal * (1. - height).sqrt()
+}
+
+/// Where a ray hit a voxel, in the coordinates of the mesh.
+struct Hit {
+    position: Vec3,
+    /// Points out of the side of the voxel that was hit.
+    normal: Vec3,
+    voxel: (usize, usize, usize),
+}
+
+/// Follows a ray through the grid one voxel at a time until it enters a filled voxel, or leaves
+/// the grid.
+fn cast<const SIZE: usize>(voxels: &VoxelGrid<SIZE>, origin: Vec3, direction: Vec3) -> Option<Hit> {
+    let start = [origin.x, origin.y, origin.z].map(|x| x * SIZE as f32);
+    let direction = [direction.x, direction.y, direction.z];
+    let mut cell = start.map(|x| x.floor() as i32);
+    let step: [i32; 3] = direction.map(|x| if x > 0. { 1 } else { -1 });
+    let mut t_max: [f32; 3] = std::array::from_fn(|i| {
+        if direction[i] == 0. {
+            f32::INFINITY
+        } else {
+            ((cell[i] + (step[i] > 0) as i32) as f32 - start[i]) / direction[i]
+        }
+    });
+    let t_delta = direction.map(|x| if x == 0. { f32::INFINITY } else { 1. / x.abs() });
+
+    loop {
+        let axis = (0..3).min_by(|a, b| t_max[*a].total_cmp(&t_max[*b])).unwrap_or(0);
+        cell[axis] += step[axis];
+        let t = t_max[axis];
+        t_max[axis] += t_delta[axis];
+        if cell.iter().any(|x| *x < 0 || *x >= SIZE as i32) {
+            return None;
+        }
+        let (x, y, z) = (cell[0] as usize, cell[1] as usize, cell[2] as usize);
+        if voxels[x][y][z].color[3] != 0 {
+            let mut normal = [0.; 3];
+            normal[axis] = -step[axis] as f32;
+            let position: [f32; 3] = std::array::from_fn(|i| (start[i] + direction[i] * t) / SIZE as f32);
+            return Some(Hit {
+                position: Vec3 { x: position[0], y: position[1], z: position[2] },
+                normal: Vec3 { x: normal[0], y: normal[1], z: normal[2] },
+                voxel: (x, y, z),
+            });
+        }
+    }
+}
+
+/// Offset of the rays from the surface they start at, so they leave the voxel they start on.
+const RAY_OFFSET: f32 = 1e-4;
+
+/// Whether `target` can be seen from `position` on a surface facing `normal`.
+fn visible<const SIZE: usize>(voxels: &VoxelGrid<SIZE>, position: Vec3, normal: Vec3, target: Vec3) -> bool {
+    let origin = position + normal * RAY_OFFSET;
+    let towards = target - origin;
+    match cast(voxels, origin, towards.normalize()) {
+        Some(hit) => hit.position.distance_squared(&origin) >= towards.dot(&towards),
+        None => true,
+    }
+}
+
+/// The light falling on a point facing `normal` straight from the lights and the sun of
+/// `lighting`, like [`crate::radiosity::apply_lights`] and
+/// [`crate::radiosity::apply_environment`] add it in the solver.
+fn direct_irradiance<const SIZE: usize>(voxels: &VoxelGrid<SIZE>, lighting: &SolverOptions, position: Vec3, normal: Vec3) -> [f32; 3] {
+    let mut received = [0.; 3];
+    for light in &lighting.lights {
+        let light = light.received_at(position, normal, |source| visible(voxels, position, normal, source));
+        for (received, light) in received.iter_mut().zip(light) {
+            *received += light;
+        }
+    }
+    if let Some(sun) = lighting.sun {
+        let towards_sun = sun.direction.normalize() * -1.;
+        let cosine = towards_sun.dot(&normal);
+        if cosine > 0. && cast(voxels, position + normal * RAY_OFFSET, towards_sun).is_none() {
+            for (received, color) in received.iter_mut().zip(sun.color) {
+                *received += color * cosine;
+            }
+        }
+    }
+    received
+}
+
+/// A single sample of the light falling on a point facing `normal`, carried over at most
+/// `bounces` bounces.
+fn sample_irradiance<const SIZE: usize>(voxels: &VoxelGrid<SIZE>, lighting: &SolverOptions, position: Vec3, normal: Vec3, bounces: u8, random: &mut Random) -> [f32; 3] {
+    let direct = direct_irradiance(voxels, lighting, position, normal);
+    let direction = cosine_direction(normal, random);
+    // Sampling along the cosine makes the light received the mean of the light leaving the
+    // surfaces seen, and those reflecting light evenly spread it like the brightness in the solver
+    let seen: [f32; 3] = match cast(voxels, position + normal * RAY_OFFSET, direction) {
+        Some(hit) if bounces > 0 => {
+            let voxel = &voxels[hit.voxel.0][hit.voxel.1][hit.voxel.2];
+            let albedo = [voxel.color[0], voxel.color[1], voxel.color[2]].map(|x| x as f32 / 256.);
+            let received = sample_irradiance(voxels, lighting, hit.position, hit.normal, bounces - 1, random);
+            std::array::from_fn(|i| albedo[i] * (voxel.emission + received[i]))
+        }
+        Some(_) => [0.; 3],
+        // Below the horizon is the ground, which is left out like in the solver
+        None => match lighting.sky {
+            Some(sky) if direction.y > 0. => std::array::from_fn(|i| sky.horizon[i] + (sky.zenith[i] - sky.horizon[i]) * direction.y),
+            _ => [0.; 3],
+        },
+    };
+    std::array::from_fn(|i| direct[i] + seen[i])
+}
+
+/// Estimates the light falling on every face by following random paths through the grid.
+///
+/// The faces are the ones from [`voxel_to_mesh`], with emitters as bright as [`Face::brightness`]
+/// starts out and surfaces reflecting light evenly in every direction, lit by the lights, sky and
+/// sun of `lighting` as well. Unlike the solver, paths are blocked by the voxels in between, and
+/// the tile is on its own without mirrored copies of it or of the lights.
+pub fn trace_irradiance<const SIZE: usize>(voxels: &VoxelGrid<SIZE>, faces: &[Face], lighting: &SolverOptions, options: &TraceOptions) -> Vec<[f32; 3]> {
+    faces.iter().enumerate().map(|(i, face)| {
+        let mut random = Random::new(options.seed ^ ((i as u64) << 20));
+        let outside = face.normal * -1.;
+        let (edge1, edge2) = (face.corners[1] - face.corners[0], face.corners[2] - face.corners[0]);
+        let mut sum = [0.; 3];
+        for _ in 0..options.samples {
+            let position = face.corners[0] + edge1 * random.next() + edge2 * random.next();
+            let sample = sample_irradiance(voxels, lighting, position, outside, options.bounces, &mut random);
+            for (sum, sample) in sum.iter_mut().zip(sample) {
+                *sum += sample;
+            }
+        }
+        sum.map(|x| x / options.samples.max(1) as f32)
+    }).collect()
+}
+
+/// The light falling on every face of a mesh according to the solver, lit and bounced the same
+/// way [`radiosity_subdivide`] does without subdividing, in the same units as [`trace_irradiance`].
+pub fn solver_irradiance(mesh: &Mesh, lighting: &SolverOptions) -> Vec<[f32; 3]> {
+    let options = SolverOptions { subdivisions: 0, lighting: true, ..lighting.clone() };
+    let lit = radiosity_subdivide(&mesh.faces, &mesh.mirror_planes, &options);
+    lit.iter().zip(&mesh.faces).map(|(lit, face)| std::array::from_fn(|i| {
+        let albedo = face.color[i] as f32 / 256.;
+        if albedo == 0. {
+            return 0.;
+        }
+        (lit.brightness[i] - face.brightness[i]) / albedo
+    })).collect()
+}
+
+/// The light falling on the faces of a tile according to the solver and the path tracer.
+pub struct Comparison {
+    pub faces: Vec<Face>,
+    pub reference: Vec<[f32; 3]>,
+    pub solver: Vec<[f32; 3]>,
+}
+
+/// Average of the colour channels.
+fn level(color: &[f32; 3]) -> f32 {
+    (color[0] + color[1] + color[2]) / 3.
+}
+
+impl Comparison {
+    /// Meshes a voxel grid without subdividing it, and lights it with both the solver and the
+    /// path tracer using the same number of bounces and the lights, sky and sun of `lighting`.
+    pub fn new<const SIZE: usize>(voxels: &VoxelGrid<SIZE>, lighting: &SolverOptions, options: &TraceOptions) -> Comparison {
+        let mesh = voxel_to_mesh(voxels, None);
+        let reference = trace_irradiance(voxels, &mesh.faces, lighting, options);
+        let solver = solver_irradiance(&mesh, &SolverOptions { iterations: options.bounces, ..lighting.clone() });
+        Comparison { faces: mesh.faces, reference, solver }
+    }
+
+    /// Total light falling on the faces according to the solver, divided by the total of the
+    /// reference, so 1 when the solver gathers as much light overall.
+    pub fn energy_ratio(&self) -> f32 {
+        let total = |values: &[[f32; 3]]| values.iter().zip(&self.faces).map(|(value, face)| level(value) * face.area()).sum::<f32>();
+        total(&self.solver) / total(&self.reference)
+    }
+
+    /// Average difference between the solver and the reference over the faces.
+    pub fn mean_absolute_error(&self) -> f32 {
+        self.differences().map(f32::abs).sum::<f32>() / self.faces.len().max(1) as f32
+    }
+
+    pub fn root_mean_square_error(&self) -> f32 {
+        (self.differences().map(|x| x * x).sum::<f32>() / self.faces.len().max(1) as f32).sqrt()
+    }
+
+    fn differences(&self) -> impl Iterator<Item=f32> + '_ {
+        self.solver.iter().zip(&self.reference).map(|(solver, reference)| level(solver) - level(reference))
+    }
+
+    /// A plain text summary, listing the `worst` faces the solver is furthest off on.
+    pub fn report(&self, worst: usize) -> String {
+        let mut report = String::new();
+        let reference_mean = self.reference.iter().map(level).sum::<f32>() / self.faces.len().max(1) as f32;
+        // Writing to a string can't fail
+        let _ = writeln!(report, "faces: {}", self.faces.len());
+        let _ = writeln!(report, "mean reference irradiance: {:.4}", reference_mean);
+        let _ = writeln!(report, "energy ratio (solver / reference): {:.4}", self.energy_ratio());
+        let _ = writeln!(report, "mean absolute error: {:.4}", self.mean_absolute_error());
+        let _ = writeln!(report, "root mean square error: {:.4}", self.root_mean_square_error());
+
+        let mut order: Vec<usize> = (0..self.faces.len()).collect();
+        order.sort_by(|a, b| {
+            let difference = |i: &usize| (level(&self.solver[*i]) - level(&self.reference[*i])).abs();
+            difference(b).total_cmp(&difference(a))
+        });
+        let _ = writeln!(report, "\nworst faces:\n{:>6} {:>24} {:>16} {:>10} {:>10}", "face", "center", "normal", "reference", "solver");
+        for i in order.into_iter().take(worst) {
+            // Adding zero turns the negative zeros of the flipped normal positive
+            let (center, normal) = (self.faces[i].center(), self.faces[i].normal * -1. + Vec3 { x: 0., y: 0., z: 0. });
+            let _ = writeln!(
+                report,
+                "{:>6} {:>7.3} {:>7.3} {:>7.3}  {:>4} {:>4} {:>4}  {:>10.4} {:>10.4}",
+                i, center.x, center.y, center.z, normal.x, normal.y, normal.z,
+                level(&self.reference[i]), level(&self.solver[i]),
+            );
+        }
+        report
+    }
+}
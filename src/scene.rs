@@ -0,0 +1,531 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use image::ImageError;
+use image::io::Reader as ImageReader;
+use image::RgbaImage;
+use json::{JsonError, JsonValue};
+use thiserror::Error;
+use crate::export_gltf::{save_glb_scene, save_gltf, save_gltf_embedded, save_gltf_embedded_scene, save_mesh, SaveMeshError, SceneNode, Transform};
+use crate::export_obj::save_obj;
+use crate::export_ply::{PlyEncoding, save_ply};
+use crate::ktx2::{Ktx2Format, save_ktx2};
+use crate::image_to_grid::{segment, SegmentShape, sweep_segment, SweepPath};
+use crate::palette::Palette;
+use crate::preview::{Camera, Sampling};
+use crate::radiosity::{AmbientOcclusion, Light, Sky, SolverOptions, Sun};
+use crate::radiosity_color::VoxelGrid;
+use crate::vector::{Vec2, Vec3};
+use crate::mesh::BakedMesh;
+use crate::voxel::{bake_mesh, voxel_to_mesh};
+
+#[derive(Debug, Error)]
+#[allow(clippy::enum_variant_names)]
+pub enum SceneError {
+    #[error("Json Error")]
+    JsonError(#[from] JsonError),
+    #[error("IO Error")]
+    IOError(#[from] std::io::Error),
+    #[error("Image Error")]
+    ImageError(#[from] ImageError),
+    #[error("Save Mesh Error")]
+    SaveMeshError(#[from] SaveMeshError),
+    #[error("Invalid scene: {0}")]
+    InvalidSceneError(String),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// A binary `.glb` with everything stored in it.
+    Glb,
+    /// A `.gltf` with its buffer and texture in separate `.bin` and `.png` files.
+    Gltf,
+    /// A single `.gltf` with its buffer and texture in data URIs.
+    GltfEmbedded,
+    /// A Wavefront `.obj` with its material in a `.mtl` and its texture in a `.png`.
+    Obj,
+    /// A binary `.ply` with baked vertex colours.
+    Ply,
+    /// A text `.ply` with baked vertex colours.
+    PlyAscii,
+}
+
+impl ExportFormat {
+    pub fn from_name(name: &str) -> Option<ExportFormat> {
+        match name {
+            "glb" => Some(ExportFormat::Glb),
+            "gltf" => Some(ExportFormat::Gltf),
+            "gltf-embedded" => Some(ExportFormat::GltfEmbedded),
+            "obj" => Some(ExportFormat::Obj),
+            "ply" => Some(ExportFormat::Ply),
+            "ply-ascii" => Some(ExportFormat::PlyAscii),
+            _ => None,
+        }
+    }
+}
+
+/// How a tile is built from its layers.
+pub enum TileShape {
+    Segment(SegmentShape),
+    Sweep(SweepPath),
+}
+
+/// A single tile to bake.
+pub struct Tile {
+    pub name: String,
+    /// Name of the layer set in [`Scene::layers`] to build the tile from.
+    pub layers: String,
+    pub shape: TileShape,
+    /// Emission of the voxels that count as lights.
+    pub brightness: f32,
+    pub solver: SolverOptions,
+    /// Path of the output files, without an extension.
+    pub output: PathBuf,
+    /// Placement of the node of the tile, in its own file or the combined one.
+    pub transform: Transform,
+}
+
+/// A batch of tiles to bake, usually loaded from a JSON file with [`load_scene`].
+///
+/// ```json
+/// {
+///     "output": "cache",
+///     "format": "glb",
+///     "palette": null,
+///     "unlit": false,
+///     "ktx2": null,
+///     "mipmaps": false,
+///     "solver": {"iterations": 4, "subdivisions": 1, "lighting": true},
+///     "layers": {
+///         "hallway": {"edge": "media/hallway_edge.png", "end": "media/hallway_edge_end.png"}
+///     },
+///     "tiles": [
+///         {"name": "hallway", "layers": "hallway", "shape": "straight", "brightness": 0.4},
+///         {"name": "hallway_sweep", "layers": "hallway", "path": {"spline": [[0, 4], [16, 12]]}, "brightness": 0.4,
+///          "translation": [1, 0, 0]}
+///     ]
+/// }
+/// ```
+///
+/// The solver can also take `"lights"`, placed in the voxels of the tile and shadowed by it:
+///
+/// ```json
+/// [
+///     {"type": "point", "position": [8, 12, 8], "color": [1, 0.9, 0.8], "intensity": 4},
+///     {"type": "spot", "position": [8, 15, 8], "direction": [0, -1, 0], "inner_angle": 20, "outer_angle": 35, "intensity": 8},
+///     {"type": "area", "corner": [6, 15.9, 6], "edges": [[4, 0, 0], [0, 0, 4]], "intensity": 1}
+/// ]
+/// ```
+///
+/// For outdoor tiles it can take a `"sky"` of one colour or blending between the `"zenith"` and
+/// `"horizon"` colours, and a `"sun"` shining along its direction, both shadowed by the tile:
+///
+/// ```json
+/// {"sky": {"zenith": [0.3, 0.4, 0.6], "horizon": [0.5, 0.5, 0.5]}, "sun": {"direction": [-1, -2, -0.5], "intensity": 1}}
+/// ```
+///
+/// `"ambient_occlusion": {"distance": 4, "multiply": false}` bakes ambient occlusion reaching
+/// that many voxels into `{output}_occlusion.png`, or into the lightmap with `multiply`. With
+/// `"lighting": false` that is a quick bake without the solver.
+///
+/// The `render` command draws every tile from a `"camera"` placed in voxels, like
+/// `{"position": [24, 34, 28], "target": [8, 4, 8], "fov": 45, "width": 256, "height": 256}`,
+/// with `"sampling"` either `"nearest"` or `"bilinear"`.
+///
+/// `palette` is either `null`, `"default"` or the path of a colormap image, and `ktx2` either
+/// `null`, `"rgba8"` or `"bc1"`. `mipmaps` writes per face mip levels into the KTX2 files, as
+/// `"rgba8"` without `ktx2`, and lets the glTF files sample them. A layer set is either an `edge` and `end` image, with the end image used for the first and last layer, or
+/// a list of 16 images. Tiles can override `solver` and set an `output` path of their own,
+/// relative paths are resolved from the directory of the scene file.
+///
+/// With `"combine": "tiles"` every tile is written as a named node of one `tiles.glb` in the
+/// output directory instead, placed by its `translation`, `rotation` and `scale`.
+pub struct Scene {
+    pub format: ExportFormat,
+    /// Path of a single file to write every tile into, without an extension.
+    pub combine: Option<PathBuf>,
+    /// Whether the materials are marked as unlit, so viewers show the baked light as is.
+    pub unlit: bool,
+    /// Whether the KTX2 files get per face mip levels that the glTF files sample, see
+    /// [`Scene::ktx2_format`].
+    pub mipmaps: bool,
+    /// When set, the textures of every tile are also written as KTX2 files next to it.
+    pub ktx2: Option<Ktx2Format>,
+    pub palette: Option<Palette>,
+    /// Where the `render` command looks at every tile from.
+    pub camera: Camera,
+    pub sampling: Sampling,
+    pub layers: HashMap<String, [RgbaImage; 16]>,
+    pub tiles: Vec<Tile>,
+}
+
+impl Scene {
+    /// The format of the KTX2 files written next to every tile, uncompressed when they are
+    /// only written for [`Scene::mipmaps`].
+    pub fn ktx2_format(&self) -> Option<Ktx2Format> {
+        self.ktx2.or(self.mipmaps.then_some(Ktx2Format::Rgba8))
+    }
+}
+
+fn invalid(message: String) -> SceneError {
+    SceneError::InvalidSceneError(message)
+}
+
+fn get_str<'a>(value: &'a JsonValue, key: &str) -> Result<&'a str, SceneError> {
+    value[key].as_str().ok_or_else(|| invalid(format!("expected a string for \"{}\"", key)))
+}
+
+fn get_f32(value: &JsonValue, key: &str) -> Result<f32, SceneError> {
+    value[key].as_f32().ok_or_else(|| invalid(format!("expected a number for \"{}\"", key)))
+}
+
+/// A boolean that is false when left out.
+fn get_flag(value: &JsonValue, key: &str) -> Result<bool, SceneError> {
+    match &value[key] {
+        JsonValue::Null => Ok(false),
+        flag => flag.as_bool().ok_or_else(|| invalid(format!("expected a boolean for \"{}\"", key))),
+    }
+}
+
+fn load_image(path: &Path) -> Result<RgbaImage, SceneError> {
+    Ok(ImageReader::open(path)?.decode()?.into_rgba8())
+}
+
+/// Loads `"default"` as the built in palette, anything else as the path of a colormap image.
+pub fn load_palette(source: &str, base: &Path) -> Result<Palette, SceneError> {
+    match source {
+        "default" => Ok(Palette::default()),
+        path => Ok(Palette::from_image(&load_image(&base.join(path))?)),
+    }
+}
+
+/// Voxels along each side of a tile, the unit lights are placed in.
+const TILE_SIZE: f32 = 16.;
+
+/// A point or offset in voxels, moved to the coordinates of the mesh.
+fn parse_point(value: &JsonValue, key: &str) -> Result<Vec3, SceneError> {
+    match (value.len(), value[0].as_f32(), value[1].as_f32(), value[2].as_f32()) {
+        (3, Some(x), Some(y), Some(z)) => Ok(Vec3 { x, y, z } * (1. / TILE_SIZE)),
+        _ => Err(invalid(format!("expected a list of 3 numbers for \"{}\"", key))),
+    }
+}
+
+/// Parses a light in voxels, where a point or spot light gives a surface facing it one voxel
+/// away `intensity` times its `color`, and an area light shines as bright as an emitting voxel
+/// of that `intensity`.
+fn parse_light(value: &JsonValue) -> Result<Light, SceneError> {
+    let intensity = get_f32(value, "intensity")?;
+    let color = parse_vector(value, "color", [1.; 3])?.map(|x| x * intensity);
+    // Moving from voxels to the coordinates of the mesh divides the distances
+    let falloff_color = color.map(|x| x / (TILE_SIZE * TILE_SIZE));
+    Ok(match get_str(value, "type")? {
+        "point" => Light::Point { position: parse_point(&value["position"], "position")?, color: falloff_color },
+        "spot" => Light::Spot {
+            position: parse_point(&value["position"], "position")?,
+            direction: parse_point(&value["direction"], "direction")?,
+            inner_angle: get_f32(value, "inner_angle")?.to_radians(),
+            outer_angle: get_f32(value, "outer_angle")?.to_radians(),
+            color: falloff_color,
+        },
+        "area" => Light::Area {
+            corner: parse_point(&value["corner"], "corner")?,
+            edges: [parse_point(&value["edges"][0], "edges")?, parse_point(&value["edges"][1], "edges")?],
+            color,
+        },
+        kind => return Err(invalid(format!("unknown light type \"{}\"", kind))),
+    })
+}
+
+/// A sky of one colour, or blending from `horizon` to `zenith`.
+fn parse_sky(value: &JsonValue) -> Result<Sky, SceneError> {
+    let zenith = parse_vector(value, "zenith", [1.; 3])?;
+    Ok(Sky { zenith, horizon: parse_vector(value, "horizon", zenith)? })
+}
+
+fn parse_sun(value: &JsonValue) -> Result<Sun, SceneError> {
+    let [x, y, z] = parse_vector(value, "direction", [0., -1., 0.])?;
+    let intensity = get_f32(value, "intensity")?;
+    Ok(Sun { direction: Vec3 { x, y, z }, color: parse_vector(value, "color", [1.; 3])?.map(|x| x * intensity) })
+}
+
+/// Ambient occlusion reaching `distance` voxels, 4 when left out.
+fn parse_ambient_occlusion(value: &JsonValue) -> Result<AmbientOcclusion, SceneError> {
+    let distance = if value["distance"].is_null() { 4. } else { get_f32(value, "distance")? };
+    Ok(AmbientOcclusion { distance: distance / TILE_SIZE, multiply: get_flag(value, "multiply")? })
+}
+
+fn parse_solver(value: &JsonValue, default: SolverOptions) -> Result<SolverOptions, SceneError> {
+    let mut options = default;
+    if !value["iterations"].is_null() {
+        options.iterations = value["iterations"].as_u8().ok_or_else(|| invalid("expected a small integer for \"iterations\"".to_string()))?;
+    }
+    if !value["subdivisions"].is_null() {
+        options.subdivisions = value["subdivisions"].as_u8().ok_or_else(|| invalid("expected a small integer for \"subdivisions\"".to_string()))?;
+    }
+    if !value["lighting"].is_null() {
+        options.lighting = value["lighting"].as_bool().ok_or_else(|| invalid("expected a boolean for \"lighting\"".to_string()))?;
+    }
+    if !value["lights"].is_null() {
+        options.lights = value["lights"].members().map(parse_light).collect::<Result<Vec<Light>, SceneError>>()?;
+    }
+    if !value["sky"].is_null() {
+        options.sky = Some(parse_sky(&value["sky"])?);
+    }
+    if !value["sun"].is_null() {
+        options.sun = Some(parse_sun(&value["sun"])?);
+    }
+    if !value["ambient_occlusion"].is_null() {
+        options.ambient_occlusion = Some(parse_ambient_occlusion(&value["ambient_occlusion"])?);
+    }
+    Ok(options)
+}
+
+fn parse_layers(value: &JsonValue, base: &Path) -> Result<[RgbaImage; 16], SceneError> {
+    if value.is_array() {
+        let images = value.members()
+            .map(|path| load_image(&base.join(path.as_str().ok_or_else(|| invalid("expected a list of image paths".to_string()))?)))
+            .collect::<Result<Vec<RgbaImage>, SceneError>>()?;
+        return images.try_into().map_err(|images: Vec<RgbaImage>| invalid(format!("expected 16 layers, got {}", images.len())));
+    }
+
+    let edge = load_image(&base.join(get_str(value, "edge")?))?;
+    let end = load_image(&base.join(get_str(value, "end")?))?;
+    Ok(std::array::from_fn(|i| if i == 0 || i == 15 { end.clone() } else { edge.clone() }))
+}
+
+fn parse_shape(name: &str) -> Result<SegmentShape, SceneError> {
+    Ok(match name {
+        "straight" => SegmentShape::Straight,
+        "left_curve" => SegmentShape::LeftCurve,
+        "right_curve" => SegmentShape::RightCurve,
+        "corridor" => SegmentShape::Corridor,
+        "t_junction" => SegmentShape::TJunction,
+        "crossing" => SegmentShape::Crossing,
+        "dead_end" => SegmentShape::DeadEnd,
+        "ramp" => SegmentShape::Ramp,
+        "stairs" => SegmentShape::Stairs,
+        "diagonal" => SegmentShape::Diagonal,
+        _ => return Err(invalid(format!("unknown shape \"{}\"", name))),
+    })
+}
+
+fn parse_points(value: &JsonValue) -> Result<Vec<Vec2>, SceneError> {
+    value.members().map(|point| match (point[0].as_f32(), point[1].as_f32()) {
+        (Some(x), Some(z)) => Ok(Vec2 { x, y: z }),
+        _ => Err(invalid("expected path points as [x, z]".to_string())),
+    }).collect()
+}
+
+fn parse_vector<const N: usize>(value: &JsonValue, key: &str, default: [f32; N]) -> Result<[f32; N], SceneError> {
+    if value[key].is_null() {
+        return Ok(default);
+    }
+    let components = value[key].members().map(|component| component.as_f32()).collect::<Option<Vec<f32>>>();
+    components.and_then(|components| components.try_into().ok())
+        .ok_or_else(|| invalid(format!("expected a list of {} numbers for \"{}\"", N, key)))
+}
+
+fn parse_transform(value: &JsonValue) -> Result<Transform, SceneError> {
+    let identity = Transform::default();
+    Ok(Transform {
+        translation: parse_vector(value, "translation", identity.translation)?,
+        rotation: parse_vector(value, "rotation", identity.rotation)?,
+        scale: parse_vector(value, "scale", identity.scale)?,
+    })
+}
+
+fn parse_tile(value: &JsonValue, base: &Path, output: &Path, solver: SolverOptions) -> Result<Tile, SceneError> {
+    let name = get_str(value, "name")?.to_string();
+    let shape = if !value["path"].is_null() {
+        let path = &value["path"];
+        if path["polyline"].is_array() {
+            TileShape::Sweep(SweepPath::Polyline(parse_points(&path["polyline"])?))
+        } else if path["spline"].is_array() {
+            TileShape::Sweep(SweepPath::Spline(parse_points(&path["spline"])?))
+        } else {
+            return Err(invalid(format!("path of tile \"{}\" needs a \"polyline\" or \"spline\"", name)));
+        }
+    } else {
+        TileShape::Segment(parse_shape(get_str(value, "shape")?)?)
+    };
+
+    Ok(Tile {
+        layers: get_str(value, "layers")?.to_string(),
+        shape,
+        brightness: get_f32(value, "brightness")?,
+        solver: parse_solver(&value["solver"], solver)?,
+        output: match value["output"].as_str() {
+            Some(path) => base.join(path),
+            None => output.join(&name),
+        },
+        transform: parse_transform(value)?,
+        name,
+    })
+}
+
+/// A camera placed in the voxels of a tile, with the field of view in degrees.
+fn parse_camera(value: &JsonValue) -> Result<Camera, SceneError> {
+    let mut camera = Camera::default();
+    if !value["position"].is_null() {
+        camera.position = parse_point(&value["position"], "position")?;
+    }
+    if !value["target"].is_null() {
+        camera.target = parse_point(&value["target"], "target")?;
+    }
+    if !value["fov"].is_null() {
+        camera.fov = get_f32(value, "fov")?.to_radians();
+    }
+    for (key, size) in [("width", &mut camera.width), ("height", &mut camera.height)] {
+        if !value[key].is_null() {
+            *size = value[key].as_u32().filter(|size| *size > 0).ok_or_else(|| invalid(format!("expected a positive integer for \"{}\"", key)))?;
+        }
+    }
+    Ok(camera)
+}
+
+/// Parses a scene, resolving relative paths from `base`.
+pub fn parse_scene(source: &str, base: &Path) -> Result<Scene, SceneError> {
+    let value = json::parse(source)?;
+
+    let format = match value["format"].as_str() {
+        None => ExportFormat::Glb,
+        Some(name) => ExportFormat::from_name(name).ok_or_else(|| invalid(format!("unknown format \"{}\"", name)))?,
+    };
+    let palette = match value["palette"].as_str() {
+        None => None,
+        Some(source) => Some(load_palette(source, base)?),
+    };
+
+    let mut layers = HashMap::new();
+    for (name, layer_set) in value["layers"].entries() {
+        layers.insert(name.to_string(), parse_layers(layer_set, base)?);
+    }
+
+    let output = base.join(value["output"].as_str().unwrap_or("cache"));
+    let combine = value["combine"].as_str().map(|name| output.join(name));
+    let solver = parse_solver(&value["solver"], SolverOptions::default())?;
+    let tiles = value["tiles"].members()
+        .map(|tile| parse_tile(tile, base, &output, solver.clone()))
+        .collect::<Result<Vec<Tile>, SceneError>>()?;
+
+    for tile in &tiles {
+        if !layers.contains_key(&tile.layers) {
+            return Err(invalid(format!("tile \"{}\" uses unknown layers \"{}\"", tile.name, tile.layers)));
+        }
+    }
+
+    let unlit = get_flag(&value, "unlit")?;
+    let mipmaps = get_flag(&value, "mipmaps")?;
+
+    let ktx2 = match value["ktx2"].as_str() {
+        None => None,
+        Some(name) => Some(Ktx2Format::from_name(name).ok_or_else(|| invalid(format!("unknown KTX2 format \"{}\"", name)))?),
+    };
+
+    let camera = parse_camera(&value["camera"])?;
+    let sampling = match value["sampling"].as_str() {
+        None => Sampling::Nearest,
+        Some(name) => Sampling::from_name(name).ok_or_else(|| invalid(format!("unknown sampling \"{}\"", name)))?,
+    };
+
+    Ok(Scene { format, combine, unlit, mipmaps, ktx2, palette, camera, sampling, layers, tiles })
+}
+
+/// Reads a scene file, resolving relative paths from the directory it is in.
+pub fn load_scene(path: &Path) -> Result<Scene, SceneError> {
+    let source = fs::read_to_string(path)?;
+    parse_scene(&source, path.parent().unwrap_or(Path::new("")))
+}
+
+/// Builds the voxel grid of a tile from its layers.
+pub fn tile_voxels(scene: &Scene, tile: &Tile) -> VoxelGrid<16> {
+    let layers = &scene.layers[&tile.layers];
+    match &tile.shape {
+        TileShape::Segment(shape) => segment(layers, *shape, tile.brightness),
+        TileShape::Sweep(path) => sweep_segment(layers, path, tile.brightness),
+    }
+}
+
+/// Bakes every tile in the scene and saves it, or saves all of them to [`Scene::combine`].
+pub fn run_scene(scene: &Scene) -> Result<(), SceneError> {
+    if let Some(combine) = &scene.combine {
+        return run_combined(scene, combine);
+    }
+
+    // The meshes reference the colormap by a relative path, so it has to be next to each of them
+    let mut colormap_directories = HashSet::new();
+
+    for tile in &scene.tiles {
+        if let Some(palette) = &scene.palette {
+            let directory = tile.output.parent().unwrap_or(Path::new("")).to_path_buf();
+            if colormap_directories.insert(directory.clone()) {
+                save_colormap(palette, &directory)?;
+            }
+        }
+
+        let mesh = bake_tile(scene, tile);
+        match scene.format {
+            ExportFormat::Glb => save_mesh(&mesh, &tile.name, tile.transform, &tile.output)?,
+            ExportFormat::Gltf => save_gltf(&mesh, &tile.name, tile.transform, &tile.output)?,
+            ExportFormat::GltfEmbedded => save_gltf_embedded(&mesh, &tile.name, tile.transform, &tile.output)?,
+            ExportFormat::Obj => save_obj(&mesh, &tile.output)?,
+            ExportFormat::Ply => save_ply(&mesh, &tile.output, PlyEncoding::BinaryLittleEndian)?,
+            ExportFormat::PlyAscii => save_ply(&mesh, &tile.output, PlyEncoding::Ascii)?,
+        }
+        if let Some(format) = scene.ktx2_format() {
+            save_ktx2(&mesh, &tile.output, format)?;
+        }
+    }
+    Ok(())
+}
+
+/// Bakes a tile with the solver options of the tile and the material settings of the scene.
+pub fn bake_tile(scene: &Scene, tile: &Tile) -> BakedMesh {
+    let mut mesh = bake_mesh(voxel_to_mesh(&tile_voxels(scene, tile), scene.palette.as_ref()), &tile.solver);
+    mesh.unlit = scene.unlit;
+    mesh.mipmaps = scene.mipmaps;
+    mesh
+}
+
+fn save_colormap(palette: &Palette, directory: &Path) -> Result<(), SceneError> {
+    fs::create_dir_all(directory)?;
+    palette.to_image().save(directory.join("colormap.png"))?;
+    Ok(())
+}
+
+/// Bakes every tile and saves them as the nodes of a single file.
+fn run_combined(scene: &Scene, output: &Path) -> Result<(), SceneError> {
+    if !matches!(scene.format, ExportFormat::Glb | ExportFormat::GltfEmbedded) {
+        return Err(invalid("combined tiles can only be saved as glb or gltf-embedded".to_string()));
+    }
+    let meshes: Vec<BakedMesh> = scene.tiles.iter().map(|tile| bake_tile(scene, tile)).collect();
+    // The KTX2 files stay next to the output of each tile, tiles outside the directory of the
+    // combined file only get its PNG textures
+    let directory = output.parent().unwrap_or(Path::new(""));
+    let ktx2: Vec<Option<String>> = scene.tiles.iter()
+        .map(|tile| tile.output.strip_prefix(directory).ok().map(|path| path.to_string_lossy().into_owned()))
+        .collect();
+    let nodes: Vec<SceneNode> = scene.tiles.iter().zip(&meshes).zip(&ktx2).map(|((tile, mesh), ktx2)| SceneNode {
+        name: &tile.name,
+        mesh,
+        transform: tile.transform,
+        ktx2: ktx2.as_deref(),
+    }).collect();
+
+    if let Some(palette) = &scene.palette {
+        save_colormap(palette, directory)?;
+    }
+    match scene.format {
+        ExportFormat::Glb => save_glb_scene(&nodes, output)?,
+        _ => save_gltf_embedded_scene(&nodes, output)?,
+    }
+    if let Some(format) = scene.ktx2_format() {
+        for (tile, mesh) in scene.tiles.iter().zip(&meshes) {
+            if let Some(directory) = tile.output.parent() {
+                fs::create_dir_all(directory)?;
+            }
+            save_ktx2(mesh, &tile.output, format)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,71 @@
+use image::Rgba;
+use crate::radiosity_color::{RadiosityColor, VoxelGrid};
+
+const EMPTY: RadiosityColor = RadiosityColor { color: Rgba([0, 0, 0, 0]), emission: 0. };
+const WHITE: Rgba<u8> = Rgba([200, 200, 200, 255]);
+const RED: Rgba<u8> = Rgba([200, 30, 30, 255]);
+const GREEN: Rgba<u8> = Rgba([30, 200, 30, 255]);
+
+fn solid(color: Rgba<u8>) -> RadiosityColor {
+    RadiosityColor { color, emission: 0. }
+}
+
+/// Fills every voxel from `min` up to but excluding `max`.
+fn fill(voxels: &mut VoxelGrid<16>, min: [usize; 3], max: [usize; 3], voxel: RadiosityColor) {
+    for plane in &mut voxels[min[0]..max[0]] {
+        for column in &mut plane[min[1]..max[1]] {
+            for cell in &mut column[min[2]..max[2]] {
+                *cell = voxel;
+            }
+        }
+    }
+}
+
+/// A box open towards +Z with a red wall at -X, a green wall at +X, a light in the middle of the
+/// ceiling and a short and a tall block on the floor.
+pub fn cornell_box() -> VoxelGrid<16> {
+    let mut voxels = [[[EMPTY; 16]; 16]; 16];
+    fill(&mut voxels, [0, 0, 0], [16, 1, 16], solid(WHITE));
+    fill(&mut voxels, [0, 15, 0], [16, 16, 16], solid(WHITE));
+    fill(&mut voxels, [0, 0, 0], [16, 16, 1], solid(WHITE));
+    fill(&mut voxels, [0, 0, 0], [1, 16, 16], solid(RED));
+    fill(&mut voxels, [15, 0, 0], [16, 16, 16], solid(GREEN));
+    fill(&mut voxels, [6, 15, 6], [10, 16, 10], RadiosityColor { color: Rgba([255, 255, 255, 255]), emission: 4. });
+    fill(&mut voxels, [3, 1, 8], [7, 5, 12], solid(WHITE));
+    fill(&mut voxels, [9, 1, 3], [13, 10, 7], solid(WHITE));
+    voxels
+}
+
+/// A closed shell of voxels that all have the colour `albedo` and give off `emission`.
+///
+/// Every face inside sees nothing but the same walls, so the light converges to the same value
+/// everywhere, see [`furnace_irradiance`].
+pub fn furnace(albedo: u8, emission: f32) -> VoxelGrid<16> {
+    let mut voxels = [[[EMPTY; 16]; 16]; 16];
+    let wall = RadiosityColor { color: Rgba([albedo, albedo, albedo, 255]), emission };
+    fill(&mut voxels, [0, 0, 0], [16, 16, 16], wall);
+    fill(&mut voxels, [1, 1, 1], [15, 15, 15], EMPTY);
+    voxels
+}
+
+/// The light falling on every face of a [`furnace`] after `bounces` bounces, for walls
+/// reflecting light evenly in every direction.
+///
+/// The walls give off `albedo / 256 * emission` like in [`crate::voxel_to_mesh`], plus
+/// `albedo / 256` of the light they receive. Seeing nothing but walls, a face receives all the
+/// light they give off, so the light received grows by a factor of the albedo with every
+/// bounce, converging to `brightness / (1 - albedo / 256)`.
+pub fn furnace_irradiance(albedo: u8, emission: f32, bounces: u8) -> f32 {
+    let reflectance = albedo as f32 / 256.;
+    let brightness = reflectance * emission;
+    brightness * (0..bounces).map(|bounce| reflectance.powi(bounce as i32)).sum::<f32>()
+}
+
+/// A white floor with a single emitting voxel `height` voxels above it, near the +X +Z corner
+/// so the mirrored copies in the solver hardly reach the floor below it.
+pub fn emitter_over_plane(height: usize) -> VoxelGrid<16> {
+    let mut voxels = [[[EMPTY; 16]; 16]; 16];
+    fill(&mut voxels, [0, 0, 0], [16, 1, 16], solid(WHITE));
+    voxels[12][1 + height][12] = RadiosityColor { color: Rgba([255, 255, 255, 255]), emission: 1. };
+    voxels
+}
@@ -0,0 +1,408 @@
+use json::JsonValue;
+
+/// Checks a binary glTF against the structural rules of the glTF 2.0 specification that the
+/// exporters have to follow, returning a description of every problem found.
+///
+/// This covers the GLB container, buffer view alignment and bounds, accessor layout and
+/// bounds, index ranges, references between objects and listing of the extensions used. It
+/// does not check anything about the images themselves.
+pub fn validate_glb(bytes: &[u8]) -> Vec<String> {
+    let mut errors = Vec::new();
+    if bytes.len() < 12 {
+        return vec![format!("file is {} bytes, shorter than the GLB header", bytes.len())];
+    }
+    if &bytes[0..4] != b"glTF" {
+        errors.push("magic is not \"glTF\"".to_string());
+    }
+    if read_u32(bytes, 4) != 2 {
+        errors.push(format!("version is {}, expected 2", read_u32(bytes, 4)));
+    }
+    if read_u32(bytes, 8) as usize != bytes.len() {
+        errors.push(format!("header length is {}, file is {} bytes", read_u32(bytes, 8), bytes.len()));
+    }
+
+    let mut chunks = Vec::new();
+    let mut offset = 12;
+    while offset + 8 <= bytes.len() {
+        let length = read_u32(bytes, offset) as usize;
+        let chunk_type = &bytes[offset + 4..offset + 8];
+        if !length.is_multiple_of(4) {
+            errors.push(format!("chunk {} has length {}, which is not a multiple of 4", chunks.len(), length));
+        }
+        if offset + 8 + length > bytes.len() {
+            errors.push(format!("chunk {} runs past the end of the file", chunks.len()));
+            break;
+        }
+        chunks.push((chunk_type, &bytes[offset + 8..offset + 8 + length]));
+        offset += 8 + length;
+    }
+    if offset != bytes.len() {
+        errors.push(format!("{} trailing bytes after the last chunk", bytes.len() - offset));
+    }
+
+    let json_chunk = match chunks.first() {
+        Some((b"JSON", data)) => data,
+        _ => {
+            errors.push("first chunk is not a JSON chunk".to_string());
+            return errors;
+        }
+    };
+    let binary = match chunks.get(1) {
+        Some((b"BIN\0", data)) => Some(*data),
+        Some(_) => {
+            errors.push("second chunk is not a BIN chunk".to_string());
+            None
+        }
+        None => None,
+    };
+
+    let document = match std::str::from_utf8(json_chunk).map(json::parse) {
+        Ok(Ok(document)) => document,
+        _ => {
+            errors.push("JSON chunk is not valid JSON".to_string());
+            return errors;
+        }
+    };
+    if document["buffers"].members().skip(1).any(|buffer| buffer["uri"].is_null()) {
+        errors.push("only the first buffer can use the BIN chunk".to_string());
+    }
+    if let (Some(binary), Some(length)) = (binary, document["buffers"][0]["byteLength"].as_usize()) {
+        if document["buffers"][0]["uri"].is_null() && (binary.len() < length || binary.len() > pad(length)) {
+            errors.push(format!("BIN chunk is {} bytes, buffer 0 declares {}", binary.len(), length));
+        }
+    }
+
+    errors.extend(validate_document(&document, &[binary.unwrap_or(&[])]));
+    errors
+}
+
+/// Checks a glTF JSON document against the data of its buffers, in order.
+pub fn validate_document(document: &JsonValue, buffers: &[&[u8]]) -> Vec<String> {
+    let mut errors = Vec::new();
+
+    if document["asset"]["version"].as_str() != Some("2.0") {
+        errors.push("asset.version is not \"2.0\"".to_string());
+    }
+
+    for (i, buffer) in document["buffers"].members().enumerate() {
+        match (buffer["byteLength"].as_usize(), buffers.get(i)) {
+            (None, _) | (Some(0), _) => errors.push(format!("buffer {} needs a byteLength of at least 1", i)),
+            (Some(length), Some(data)) if data.len() < length => {
+                errors.push(format!("buffer {} declares {} bytes, but has {}", i, length, data.len()))
+            }
+            (Some(_), None) => errors.push(format!("buffer {} has no data", i)),
+            _ => {}
+        }
+    }
+
+    for (i, view) in document["bufferViews"].members().enumerate() {
+        let offset = view["byteOffset"].as_usize().unwrap_or(0);
+        let length = view["byteLength"].as_usize().unwrap_or(0);
+        if length == 0 {
+            errors.push(format!("bufferView {} needs a byteLength of at least 1", i));
+        }
+        match view["buffer"].as_usize().and_then(|buffer| document["buffers"][buffer]["byteLength"].as_usize()) {
+            None => errors.push(format!("bufferView {} references a missing buffer", i)),
+            Some(buffer_length) if offset + length > buffer_length => {
+                errors.push(format!("bufferView {} ends at {}, past the end of its buffer at {}", i, offset + length, buffer_length))
+            }
+            _ => {}
+        }
+        if !offset.is_multiple_of(4) {
+            errors.push(format!("bufferView {} has offset {}, which is not 4 byte aligned", i, offset));
+        }
+        if let Some(stride) = view["byteStride"].as_usize() {
+            if !(4..=252).contains(&stride) || !stride.is_multiple_of(4) {
+                errors.push(format!("bufferView {} has an invalid byteStride of {}", i, stride));
+            }
+        }
+        if let Some(target) = view["target"].as_u32() {
+            if target != 34962 && target != 34963 {
+                errors.push(format!("bufferView {} has an invalid target of {}", i, target));
+            }
+        }
+    }
+
+    for (i, accessor) in document["accessors"].members().enumerate() {
+        errors.extend(validate_accessor(document, buffers, accessor).into_iter().map(|error| format!("accessor {} {}", i, error)));
+    }
+
+    for (i, mesh) in document["meshes"].members().enumerate() {
+        for (j, primitive) in mesh["primitives"].members().enumerate() {
+            errors.extend(validate_primitive(document, buffers, primitive).into_iter().map(|error| format!("mesh {} primitive {} {}", i, j, error)));
+        }
+    }
+
+    for (i, node) in document["nodes"].members().enumerate() {
+        if !node["mesh"].is_null() && !is_index(document, "meshes", &node["mesh"]) {
+            errors.push(format!("node {} references a missing mesh", i));
+        }
+    }
+    for (i, scene) in document["scenes"].members().enumerate() {
+        if scene["nodes"].members().any(|node| !is_index(document, "nodes", node)) {
+            errors.push(format!("scene {} references a missing node", i));
+        }
+    }
+    if !document["scene"].is_null() && !is_index(document, "scenes", &document["scene"]) {
+        errors.push("scene references a missing scene".to_string());
+    }
+    for (i, image) in document["images"].members().enumerate() {
+        match (image["uri"].is_null(), image["bufferView"].is_null()) {
+            (false, false) | (true, true) => errors.push(format!("image {} needs exactly one of uri and bufferView", i)),
+            (true, false) => {
+                if !is_index(document, "bufferViews", &image["bufferView"]) {
+                    errors.push(format!("image {} references a missing bufferView", i));
+                }
+                if image["mimeType"].is_null() {
+                    errors.push(format!("image {} is stored in a bufferView without a mimeType", i));
+                }
+            }
+            _ => {}
+        }
+    }
+    for (i, sampler) in document["samplers"].members().enumerate() {
+        if sampler["magFilter"].as_u32().is_some_and(|filter| filter != 9728 && filter != 9729) {
+            errors.push(format!("sampler {} has an invalid magFilter", i));
+        }
+        if sampler["minFilter"].as_u32().is_some_and(|filter| !matches!(filter, 9728 | 9729 | 9984..=9987)) {
+            errors.push(format!("sampler {} has an invalid minFilter", i));
+        }
+    }
+    for (i, texture) in document["textures"].members().enumerate() {
+        if !texture["source"].is_null() && !is_index(document, "images", &texture["source"]) {
+            errors.push(format!("texture {} references a missing image", i));
+        }
+        if !texture["sampler"].is_null() && !is_index(document, "samplers", &texture["sampler"]) {
+            errors.push(format!("texture {} references a missing sampler", i));
+        }
+        let basisu = &texture["extensions"]["KHR_texture_basisu"]["source"];
+        if !basisu.is_null() && !is_index(document, "images", basisu) {
+            errors.push(format!("texture {} references a missing KTX2 image", i));
+        }
+        for (extension, _) in texture["extensions"].entries() {
+            if !document["extensionsUsed"].members().any(|used| used == extension) {
+                errors.push(format!("texture {} uses {}, which is not in extensionsUsed", i, extension));
+            }
+        }
+    }
+    for (i, material) in document["materials"].members().enumerate() {
+        let textures = [
+            &material["pbrMetallicRoughness"]["baseColorTexture"]["index"],
+            &material["emissiveTexture"]["index"],
+            &material["occlusionTexture"]["index"],
+        ];
+        for texture in textures {
+            if !texture.is_null() && !is_index(document, "textures", texture) {
+                errors.push(format!("material {} references a missing texture", i));
+            }
+        }
+        let factor = &material["emissiveFactor"];
+        if !factor.is_null() && (factor.len() != 3 || factor.members().any(|x| !x.as_f32().is_some_and(|x| (0. ..=1.).contains(&x)))) {
+            errors.push(format!("material {} needs an emissiveFactor of 3 numbers from 0 to 1", i));
+        }
+        for (extension, _) in material["extensions"].entries() {
+            if !document["extensionsUsed"].members().any(|used| used == extension) {
+                errors.push(format!("material {} uses {}, which is not in extensionsUsed", i, extension));
+            }
+        }
+    }
+    for required in document["extensionsRequired"].members() {
+        if !document["extensionsUsed"].members().any(|used| used == required) {
+            errors.push(format!("{} is required but not in extensionsUsed", required));
+        }
+    }
+
+    errors
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]])
+}
+
+fn pad(length: usize) -> usize {
+    length.div_ceil(4) * 4
+}
+
+fn is_index(document: &JsonValue, collection: &str, index: &JsonValue) -> bool {
+    index.as_usize().is_some_and(|index| index < document[collection].len())
+}
+
+fn component_count(accessor_type: &str) -> Option<usize> {
+    match accessor_type {
+        "SCALAR" => Some(1),
+        "VEC2" => Some(2),
+        "VEC3" => Some(3),
+        "VEC4" | "MAT2" => Some(4),
+        "MAT3" => Some(9),
+        "MAT4" => Some(16),
+        _ => None,
+    }
+}
+
+fn component_size(component_type: u32) -> Option<usize> {
+    match component_type {
+        5120 | 5121 => Some(1),
+        5122 | 5123 => Some(2),
+        5125 | 5126 => Some(4),
+        _ => None,
+    }
+}
+
+fn read_component(data: &[u8], component_type: u32) -> f64 {
+    match component_type {
+        5120 => data[0] as i8 as f64,
+        5121 => data[0] as f64,
+        5122 => i16::from_le_bytes([data[0], data[1]]) as f64,
+        5123 => u16::from_le_bytes([data[0], data[1]]) as f64,
+        5125 => u32::from_le_bytes([data[0], data[1], data[2], data[3]]) as f64,
+        _ => f32::from_le_bytes([data[0], data[1], data[2], data[3]]) as f64,
+    }
+}
+
+/// The values of an accessor as rows of components, or nothing when its data can't be read.
+fn accessor_values(document: &JsonValue, buffers: &[&[u8]], accessor: &JsonValue) -> Option<Vec<Vec<f64>>> {
+    let component_type = accessor["componentType"].as_u32()?;
+    let size = component_size(component_type)?;
+    let components = component_count(accessor["type"].as_str()?)?;
+    let count = accessor["count"].as_usize()?;
+    let view = &document["bufferViews"][accessor["bufferView"].as_usize()?];
+    let data = buffers.get(view["buffer"].as_usize()?)?;
+    let start = view["byteOffset"].as_usize().unwrap_or(0) + accessor["byteOffset"].as_usize().unwrap_or(0);
+    let stride = view["byteStride"].as_usize().unwrap_or(size * components);
+
+    (0..count).map(|element| {
+        (0..components).map(|component| {
+            let offset = start + element * stride + component * size;
+            data.get(offset..offset + size).map(|bytes| read_component(bytes, component_type))
+        }).collect()
+    }).collect()
+}
+
+fn validate_accessor(document: &JsonValue, buffers: &[&[u8]], accessor: &JsonValue) -> Vec<String> {
+    let mut errors = Vec::new();
+    let component_type = accessor["componentType"].as_u32().unwrap_or(0);
+    let (size, components) = match (component_size(component_type), accessor["type"].as_str().and_then(component_count)) {
+        (Some(size), Some(components)) => (size, components),
+        _ => return vec!["has an invalid componentType or type".to_string()],
+    };
+    let count = accessor["count"].as_usize().unwrap_or(0);
+    if count == 0 {
+        errors.push("needs a count of at least 1".to_string());
+    }
+
+    let view = &document["bufferViews"][accessor["bufferView"].as_usize().unwrap_or(usize::MAX)];
+    if view.is_null() {
+        return vec!["references a missing bufferView".to_string()];
+    }
+    let offset = accessor["byteOffset"].as_usize().unwrap_or(0);
+    let view_offset = view["byteOffset"].as_usize().unwrap_or(0);
+    if !offset.is_multiple_of(size) || !(view_offset + offset).is_multiple_of(size) {
+        errors.push(format!("is not aligned to its component size of {}", size));
+    }
+    let element_size = size * components;
+    let stride = view["byteStride"].as_usize().unwrap_or(element_size);
+    if stride < element_size {
+        errors.push(format!("has elements of {} bytes, larger than the byteStride of {}", element_size, stride));
+    }
+    let length = if count == 0 { 0 } else { offset + stride * (count - 1) + element_size };
+    if length > view["byteLength"].as_usize().unwrap_or(0) {
+        errors.push(format!("needs {} bytes, but its bufferView has {}", length, view["byteLength"]));
+        return errors;
+    }
+
+    let min = &accessor["min"];
+    let max = &accessor["max"];
+    if min.is_null() != max.is_null() {
+        errors.push("needs both min and max or neither".to_string());
+    } else if !min.is_null() {
+        if min.len() != components || max.len() != components {
+            errors.push(format!("min and max need {} components", components));
+        } else if let Some(values) = accessor_values(document, buffers, accessor) {
+            for component in 0..components {
+                let actual_min = values.iter().map(|value| value[component]).fold(f64::INFINITY, f64::min);
+                let actual_max = values.iter().map(|value| value[component]).fold(f64::NEG_INFINITY, f64::max);
+                // The JSON is written from f32 values, so compare at that precision
+                if min[component].as_f32().map(|min| min as f64) != Some(actual_min as f32 as f64) {
+                    errors.push(format!("min[{}] is {}, the data has {}", component, min[component], actual_min));
+                }
+                if max[component].as_f32().map(|max| max as f64) != Some(actual_max as f32 as f64) {
+                    errors.push(format!("max[{}] is {}, the data has {}", component, max[component], actual_max));
+                }
+            }
+        }
+    }
+    errors
+}
+
+fn validate_primitive(document: &JsonValue, buffers: &[&[u8]], primitive: &JsonValue) -> Vec<String> {
+    let mut errors = Vec::new();
+    let attributes = &primitive["attributes"];
+    let position = &document["accessors"][attributes["POSITION"].as_usize().unwrap_or(usize::MAX)];
+    if position.is_null() {
+        return vec!["has no POSITION accessor".to_string()];
+    }
+    if position["min"].is_null() || position["max"].is_null() {
+        errors.push("has a POSITION accessor without min and max".to_string());
+    }
+    let vertex_count = position["count"].as_usize().unwrap_or(0);
+
+    for (name, index) in attributes.entries() {
+        let accessor = &document["accessors"][index.as_usize().unwrap_or(usize::MAX)];
+        if accessor.is_null() {
+            errors.push(format!("attribute {} references a missing accessor", name));
+            continue;
+        }
+        if accessor["count"].as_usize() != Some(vertex_count) {
+            errors.push(format!("attribute {} has {} elements, POSITION has {}", name, accessor["count"], vertex_count));
+        }
+        let view = &document["bufferViews"][accessor["bufferView"].as_usize().unwrap_or(usize::MAX)];
+        if view["target"].as_u32().is_some_and(|target| target != 34962) {
+            errors.push(format!("attribute {} is in a bufferView that is not an ARRAY_BUFFER", name));
+        }
+        let expected_type = match name {
+            "POSITION" | "NORMAL" => Some("VEC3"),
+            "TANGENT" => Some("VEC4"),
+            _ if name.starts_with("TEXCOORD_") => Some("VEC2"),
+            _ => None,
+        };
+        if expected_type.is_some_and(|expected| accessor["type"].as_str() != Some(expected)) {
+            errors.push(format!("attribute {} has type {}, expected {}", name, accessor["type"], expected_type.unwrap_or_default()));
+        }
+        if name == "NORMAL" {
+            let values = accessor_values(document, buffers, accessor).unwrap_or_default();
+            if values.iter().any(|normal| (normal.iter().map(|x| x * x).sum::<f64>() - 1.).abs() > 0.0005) {
+                errors.push("attribute NORMAL has vectors that are not unit length".to_string());
+            }
+        }
+    }
+
+    if !primitive["indices"].is_null() {
+        let accessor = &document["accessors"][primitive["indices"].as_usize().unwrap_or(usize::MAX)];
+        if accessor.is_null() {
+            errors.push("indices reference a missing accessor".to_string());
+        } else {
+            if accessor["type"].as_str() != Some("SCALAR") || !matches!(accessor["componentType"].as_u32(), Some(5121 | 5123 | 5125)) {
+                errors.push("indices need to be unsigned SCALAR values".to_string());
+            }
+            let view = &document["bufferViews"][accessor["bufferView"].as_usize().unwrap_or(usize::MAX)];
+            if !view["byteStride"].is_null() {
+                errors.push("indices are in a bufferView with a byteStride".to_string());
+            }
+            if view["target"].as_u32().is_some_and(|target| target != 34963) {
+                errors.push("indices are in a bufferView that is not an ELEMENT_ARRAY_BUFFER".to_string());
+            }
+            let values = accessor_values(document, buffers, accessor).unwrap_or_default();
+            if values.iter().any(|index| index[0] as usize >= vertex_count) {
+                errors.push(format!("indices go past the {} vertices", vertex_count));
+            }
+            if !values.len().is_multiple_of(3) {
+                errors.push(format!("has {} indices, which is not a whole number of triangles", values.len()));
+            }
+        }
+    }
+
+    if !primitive["material"].is_null() && !is_index(document, "materials", &primitive["material"]) {
+        errors.push("references a missing material".to_string());
+    }
+    errors
+}
@@ -31,16 +31,24 @@ impl Vec3 {
 
     pub fn distance_squared(&self, other: &Self) -> f32 {
         let diff = self - other;
-        return diff.x * diff.x + diff.y * diff.y + diff.z * diff.z;
+        diff.x * diff.x + diff.y * diff.y + diff.z * diff.z
     }
 
     pub fn dot(&self, other: &Self) -> f32 {
-        return self.x * other.x + self.y * other.y + self.z * other.z;
+        self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn cross(&self, other: &Self) -> Self {
+        Vec3 {
+            x: self.y * other.z - self.z * other.y,
+            y: self.z * other.x - self.x * other.z,
+            z: self.x * other.y - self.y * other.x,
+        }
     }
 
     pub fn normalize(&self) -> Self {
         let distance = self.distance_squared(&Vec3{x: 0., y: 0., z:0.}).sqrt();
-        return Vec3 {
+        Vec3 {
             x: self.x / distance,
             y: self.y / distance,
             z: self.z / distance
@@ -123,6 +131,17 @@ pub struct Vec2 {
     pub y: f32,
 }
 
+impl Vec2 {
+    pub fn distance_squared(&self, other: &Self) -> f32 {
+        let diff = *self - *other;
+        diff.dot(&diff)
+    }
+
+    pub fn dot(&self, other: &Self) -> f32 {
+        self.x * other.x + self.y * other.y
+    }
+}
+
 impl Add for Vec2 {
     type Output = Vec2;
 
@@ -155,3 +174,14 @@ impl Mul<f32> for Vec2 {
         }
     }
 }
+
+impl Sub for Vec2 {
+    type Output = Vec2;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Vec2 {
+            x: self.x - rhs.x,
+            y: self.y - rhs.y,
+        }
+    }
+}
@@ -1,19 +1,28 @@
-use image::{DynamicImage, Pixel};
-use crate::export_gltf::{save_mesh, SaveMeshError};
-use crate::radiosity::{Face, radiosity_subdivide, simulate_radiosity};
+use image::{DynamicImage, Pixel, Rgba, RgbaImage};
+use crate::mesh::{BakedMesh, Emissive, Mesh, MeshTexture};
+use crate::palette::Palette;
+use crate::radiosity::{ambient_occlusion, Face, lightmap, MirrorPlanes, mirrored_faces, radiosity_subdivide, SolverOptions};
 use crate::vector::{Vec2, Vec3};
-use std::default::Default;
-use crate::radiosity_color::RadiosityColor;
+use crate::radiosity_color::VoxelGrid;
 
 struct CubeSides {
     edge: u8,
     front: bool,
 }
 
-const IMAGE_WIDTH: i32 = 64;
-const IMAGE_MARGIN: i32 = 256;
-const FRAC_1_IMAGE_WIDTH: f32 = 1. / IMAGE_WIDTH as f32;
-const FRAC_1_IMAGE_MARGIN: f32 = 1. / IMAGE_MARGIN as f32;
+/// Smallest number of cells along each side of the lightmap.
+const MIN_LIGHTMAP_CELLS: u32 = 64;
+
+/// Number of cells along each side of the lightmap of a mesh with `faces` faces, one cell for
+/// every face, kept a power of two so the mip levels stay whole.
+fn lightmap_cells(faces: usize) -> u32 {
+    ((faces as f64).sqrt().ceil() as u32).next_power_of_two().max(MIN_LIGHTMAP_CELLS)
+}
+
+/// Width and height of the lightmap of a mesh with `faces` faces, each split `subdivisions` times.
+fn lightmap_size(faces: usize, subdivisions: u8) -> u32 {
+    lightmap_cells(faces) << subdivisions
+}
 
 impl CubeSides {
     fn new() -> Self {
@@ -24,7 +33,7 @@ impl CubeSides {
     }
 
     fn get_item(&self) -> CubeSide {
-        let mut normal: (Vec3, Vec3, Vec3) = match self.edge {
+        let normal: (Vec3, Vec3, Vec3) = match self.edge {
             0 => (Vec3 { x: 1., y: 0., z: 0. }, Vec3 { x: 0., y: 1., z: 0. }, Vec3 { x: 0., y: 0., z: 1. }),
             1 => (Vec3 { x: 0., y: 1., z: 0. }, Vec3 { x: 0., y: 0., z: 1. }, Vec3 { x: 1., y: 0., z: 0. }),
             2 => (Vec3 { x: 0., y: 0., z: 1. }, Vec3 { x: 1., y: 0., z: 0. }, Vec3 { x: 0., y: 1., z: 0. }),
@@ -38,18 +47,15 @@ impl CubeSides {
             normal.1 + normal.2
         ];
 
-        let final_normal: Vec3;
-        if self.front {
-            for i in 0..positions.len() {
-                positions[i] += normal.0;
+        let final_normal = if self.front {
+            for position in positions.iter_mut() {
+                *position += normal.0;
             }
 
-            final_normal = (
-                normal.0 * -1.
-            );
+            normal.0 * -1.
         } else {
-            final_normal = normal.0
-        }
+            normal.0
+        };
 
         let indices = if self.front {
             [0, 1, 2, 2, 1, 3]
@@ -57,7 +63,7 @@ impl CubeSides {
             [0, 2, 1, 1, 2, 3]
         };
 
-        let offset_multiplier: i8 = if (self.front) { 1 } else { -1 };
+        let offset_multiplier: i8 = if self.front { 1 } else { -1 };
         let offset = (normal.0.x as i8 * offset_multiplier, normal.0.y as i8 * offset_multiplier, normal.0.z as i8 * offset_multiplier);
 
         CubeSide {
@@ -73,6 +79,7 @@ impl CubeSides {
                 id: 0,
                 last_iteration_brightness: [0.0; 3],
                 color: [0, 0, 0, 255].into(),
+                emission: 0.,
             },
         }
     }
@@ -101,33 +108,68 @@ impl Iterator for CubeSides {
         } else {
             self.front = true;
         }
-        return Option::Some(item);
+        Option::Some(item)
     }
 }
 
-fn is_empty_or_out_of_bounds<const SIZE: usize>(voxels: &[[[RadiosityColor; SIZE]; SIZE]; SIZE], coords: (i32, i32, i32)) -> bool {
+fn is_empty_or_out_of_bounds<const SIZE: usize>(voxels: &VoxelGrid<SIZE>, coords: (i32, i32, i32)) -> bool {
     if coords.0 < 0 || coords.1 < 0 || coords.2 < 0 {
         return false;
     }
     if coords.0 >= SIZE as i32 || coords.1 >= SIZE as i32 || coords.2 >= SIZE as i32 {
         return false;
     }
-    return voxels[coords.0 as usize][coords.1 as usize][coords.2 as usize].color[3] == 0;
+    voxels[coords.0 as usize][coords.1 as usize][coords.2 as usize].color[3] == 0
 }
 
-pub fn voxel_to_mesh<const SIZE: usize>(voxels: [[[RadiosityColor; SIZE]; SIZE]; SIZE], filename: String) -> Result<(), SaveMeshError> {
+/// Number of sides of the voxels that face an empty voxel.
+fn count_faces<const SIZE: usize>(voxels: &VoxelGrid<SIZE>) -> usize {
+    let mut count = 0;
+    for x in 0..SIZE {
+        for y in 0..SIZE {
+            for z in 0..SIZE {
+                if voxels[x][y][z].color[3] != 0 {
+                    count += CubeSides::new().filter(|cube| is_empty_or_out_of_bounds(voxels, (
+                        cube.offset.0 as i32 + x as i32,
+                        cube.offset.1 as i32 + y as i32,
+                        cube.offset.2 as i32 + z as i32
+                    ))).count();
+                }
+            }
+        }
+    }
+    count
+}
+
+/// Builds a mesh out of the sides of the voxels that face an empty voxel.
+///
+/// With a `palette` the voxel colours are quantized to it and the texture coordinates point
+/// into its colormap instead of the lightmap.
+pub fn voxel_to_mesh<const SIZE: usize>(voxels: &VoxelGrid<SIZE>, palette: Option<&Palette>) -> Mesh {
     let mut positions: Vec<Vec3> = Vec::new();
     let mut normals: Vec<Vec3> = Vec::new();
     let mut texture_coordinates: Vec<Vec2> = Vec::new();
+    let mut lightmap_coordinates: Vec<Vec2> = Vec::new();
     let mut indexes: Vec<usize> = Vec::new();
     let mut faces: Vec<Face> = Vec::new();
 
+    // Every face gets a cell of the lightmap, so the faces are counted before laying them out
+    let cells = lightmap_cells(count_faces(voxels)) as usize;
+    let cell = 1. / cells as f32;
+    let margin = cell / 4.;
     let mut face_index = 0;
 
     for x in 0..SIZE {
         for y in 0..SIZE {
             for z in 0..SIZE {
                 if voxels[x][y][z].color[3] != 0 {
+                    let (palette_index, color) = match palette {
+                        Some(palette) => {
+                            let (index, color) = palette.quantize(&voxels[x][y][z].color);
+                            (Some(index), color)
+                        }
+                        None => (None, voxels[x][y][z].color)
+                    };
                     let base_position = Vec3 {
                         x: x as f32 / SIZE as f32,
                         y: y as f32 / SIZE as f32,
@@ -135,7 +177,7 @@ pub fn voxel_to_mesh<const SIZE: usize>(voxels: [[[RadiosityColor; SIZE]; SIZE];
                     };
 
                     for cube in CubeSides::new() {
-                        if is_empty_or_out_of_bounds(&voxels, (
+                        if is_empty_or_out_of_bounds(voxels, (
                             (cube.offset.0) as i32 + x as i32,
                             (cube.offset.1) as i32 + y as i32,
                             (cube.offset.2) as i32 + z as i32
@@ -146,17 +188,21 @@ pub fn voxel_to_mesh<const SIZE: usize>(voxels: [[[RadiosityColor; SIZE]; SIZE];
                             indexes.extend(cube.indices.map(|x| x + length));
 
                             let texture_offset = Vec2 {
-                                x: (face_index / IMAGE_WIDTH) as f32 / IMAGE_WIDTH as f32,
-                                y: (face_index % IMAGE_WIDTH) as f32 / IMAGE_WIDTH as f32,
+                                x: (face_index / cells) as f32 * cell,
+                                y: (face_index % cells) as f32 * cell,
                             };
                             let face_coordinates = [
-                                Vec2 { x: FRAC_1_IMAGE_MARGIN, y: FRAC_1_IMAGE_MARGIN } + texture_offset,
-                                Vec2 { x: FRAC_1_IMAGE_WIDTH - FRAC_1_IMAGE_MARGIN, y: FRAC_1_IMAGE_MARGIN } + texture_offset,
-                                Vec2 { x: FRAC_1_IMAGE_MARGIN, y: FRAC_1_IMAGE_WIDTH - FRAC_1_IMAGE_MARGIN } + texture_offset,
-                                Vec2 { x: FRAC_1_IMAGE_WIDTH - FRAC_1_IMAGE_MARGIN, y: FRAC_1_IMAGE_WIDTH - FRAC_1_IMAGE_MARGIN } + texture_offset,
+                                Vec2 { x: margin, y: margin } + texture_offset,
+                                Vec2 { x: cell - margin, y: margin } + texture_offset,
+                                Vec2 { x: margin, y: cell - margin } + texture_offset,
+                                Vec2 { x: cell - margin, y: cell - margin } + texture_offset,
                             ];
 
-                            texture_coordinates.extend(face_coordinates.clone());
+                            lightmap_coordinates.extend(face_coordinates);
+                            match (palette, palette_index) {
+                                (Some(palette), Some(index)) => texture_coordinates.extend([palette.texture_coordinate(index); 4]),
+                                _ => texture_coordinates.extend(face_coordinates),
+                            }
                             face_index += 1;
 
                             let mut face = cube.face;
@@ -165,8 +211,9 @@ pub fn voxel_to_mesh<const SIZE: usize>(voxels: [[[RadiosityColor; SIZE]; SIZE];
                                 |x| x * (1.0 / SIZE as f32) + base_position);
                             face.id = faces.len() as u32;
                             face.texture_position = face_coordinates;
-                            face.brightness = voxels[x][y][z].color.to_rgb().0.map(|i| (i as f32) / 256. * voxels[x][y][z].emission);
-                            face.color = voxels[x][y][z].color;
+                            face.brightness = color.to_rgb().0.map(|i| (i as f32) / 256. * voxels[x][y][z].emission);
+                            face.color = color;
+                            face.emission = voxels[x][y][z].emission;
                             faces.push(face);
                         }
                     }
@@ -175,14 +222,111 @@ pub fn voxel_to_mesh<const SIZE: usize>(voxels: [[[RadiosityColor; SIZE]; SIZE];
         }
     }
 
-    let texture = radiosity_subdivide(&mut faces, 4, 1);
+    Mesh {
+        positions,
+        normals,
+        texture_coordinates,
+        lightmap_coordinates,
+        indices: indexes,
+        faces,
+        palette: palette.cloned(),
+        mirror_planes: MirrorPlanes::new(voxels),
+    }
+}
+
+/// Runs the solver on the faces of a mesh and stores the result in a lightmap.
+///
+/// With a palette the light is stored per vertex and in a lightmap of its own instead, and the
+/// mesh samples its colours from a `colormap.png` next to the exported file.
+///
+/// Ambient occlusion is baked against the faces of the mesh and their mirrored copies, and either darkens the light or
+/// ends up in [`BakedMesh::occlusion`].
+pub fn bake_mesh(mesh: Mesh, options: &SolverOptions) -> BakedMesh {
+    let size = lightmap_size(mesh.faces.len(), options.subdivisions);
+    let mut lit_faces = radiosity_subdivide(&mesh.faces, &mesh.mirror_planes, options);
+    let mut occlusion = None;
+    if let Some(settings) = options.ambient_occlusion {
+        let open = ambient_occlusion(&lit_faces, &mirrored_faces(&mesh.faces), settings.distance);
+        if settings.multiply {
+            for (face, open) in lit_faces.iter_mut().zip(open) {
+                face.brightness = face.brightness.map(|x| x * open);
+            }
+        } else {
+            let faces: Vec<Face> = lit_faces.iter().zip(open).map(|(face, open)| Face { brightness: [open; 3], ..*face }).collect();
+            occlusion = Some(DynamicImage::ImageRgba8(lightmap(&faces, size)));
+        }
+    }
+    let mut baked = BakedMesh {
+        mesh,
+        colors: None,
+        texture: MeshTexture::Embedded(DynamicImage::ImageRgba8(lightmap(&lit_faces, size))),
+        light: None,
+        emissive: None,
+        occlusion,
+        unlit: false,
+        mipmaps: false,
+        lit_faces,
+        subdivisions: options.subdivisions,
+    };
+
+    if baked.mesh.palette.is_some() {
+        // Dividing out the albedo leaves only the light, the palette supplies the colour.
+        let colors: Vec<[f32; 3]> = (0..baked.mesh.positions.len()).map(|vertex| light(baked.vertex_face(vertex))).collect();
+        let light_faces: Vec<Face> = baked.lit_faces.iter().map(|face| Face { brightness: light(face), ..*face }).collect();
+        baked.colors = Some(colors);
+        baked.light = Some(DynamicImage::ImageRgba8(lightmap(&light_faces, size)));
+        baked.texture = MeshTexture::External("colormap.png".to_string());
+    }
+    baked.emissive = emissive(&baked);
+    baked
+}
 
-    save_mesh(
-        filename,
-        &positions,
-        &normals,
-        &texture_coordinates,
-        &indexes,
-        DynamicImage::ImageRgba8(texture),
-    )
+/// Draws the emitting faces into a texture laid out like the lightmap.
+fn emissive(baked: &BakedMesh) -> Option<Emissive> {
+    let strength = baked.mesh.faces.iter().map(|face| face.emission).fold(0., f32::max);
+    if strength <= 0. {
+        return None;
+    }
+    let faces: Vec<Face> = baked.lit_faces.iter().map(|face| Face {
+        brightness: face.color.to_rgb().0.map(|i| i as f32 / 256. * face.emission / strength),
+        ..*face
+    }).collect();
+    let texture = lightmap(&faces, lightmap_size(baked.mesh.faces.len(), baked.subdivisions));
+    Some(Emissive { texture: DynamicImage::ImageRgba8(texture), strength })
+}
+
+/// The light falling on a face, without its own colour.
+fn light(face: &Face) -> [f32; 3] {
+    std::array::from_fn(|i| {
+        if face.color[i] == 0 {
+            0.
+        } else {
+            (face.brightness[i] / (face.color[i] as f32 / 256.)).min(1.)
+        }
+    })
+}
+
+/// The mip levels of a texture laid out like the lightmap, starting with the texture itself.
+///
+/// Every face has a square of `1 << subdivisions` pixels, so halving the size averages only
+/// pixels of the same face until each face is a single pixel. The levels stop there, as any
+/// further level would mix neighbouring faces.
+pub fn lightmap_mip_levels(texture: &RgbaImage, subdivisions: u8) -> Vec<RgbaImage> {
+    let mut levels = vec![texture.clone()];
+    for _ in 0..subdivisions {
+        let previous = &levels[levels.len() - 1];
+        let level = RgbaImage::from_fn(previous.width() / 2, previous.height() / 2, |x, y| {
+            // The lightmap leaves the pixels of unused faces transparent, keep them out of the average
+            let pixels: Vec<&Rgba<u8>> = [(0, 0), (1, 0), (0, 1), (1, 1)].iter()
+                .map(|(dx, dy)| previous.get_pixel(x * 2 + dx, y * 2 + dy))
+                .filter(|pixel| pixel[3] != 0)
+                .collect();
+            if pixels.is_empty() {
+                return Rgba([0, 0, 0, 0]);
+            }
+            Rgba(std::array::from_fn(|i| (pixels.iter().map(|pixel| pixel[i] as u32).sum::<u32>() / pixels.len() as u32) as u8))
+        });
+        levels.push(level);
+    }
+    levels
 }
@@ -0,0 +1,31 @@
+// Every test crate only uses some of the helpers
+#![allow(dead_code)]
+
+use voxel_generator::radiosity::Face;
+use voxel_generator::{bake_mesh, BakedMesh, Palette, RadiosityColor, SolverOptions, VoxelGrid, voxel_to_mesh};
+
+pub const EMPTY: RadiosityColor = RadiosityColor { color: image::Rgba([0, 0, 0, 0]), emission: 0. };
+
+/// A grey floor with a single light hanging above it.
+pub fn lit_room() -> VoxelGrid<4> {
+    let mut voxels = [[[EMPTY; 4]; 4]; 4];
+    for plane in voxels.iter_mut() {
+        for row in plane.iter_mut() {
+            row[0] = RadiosityColor { color: [128, 128, 128, 255].into(), emission: 0. };
+        }
+    }
+    voxels[1][2][1] = RadiosityColor { color: [255, 255, 255, 255].into(), emission: 1. };
+    voxels
+}
+
+pub fn bake(palette: Option<&Palette>) -> BakedMesh {
+    let options = SolverOptions { iterations: 1, ..SolverOptions::default() };
+    bake_mesh(voxel_to_mesh(&lit_room(), palette), &options)
+}
+
+/// Index of the face on top of the floor voxel at `x`, `z` of the 16 voxel test scenes.
+pub fn floor_face(faces: &[Face], x: usize, z: usize) -> usize {
+    faces.iter().position(|face| {
+        face.normal.y < 0. && face.corners[0].y == 1. / 16. && face.corners[0].x == x as f32 / 16. && face.corners[0].z == z as f32 / 16.
+    }).unwrap()
+}
@@ -0,0 +1,230 @@
+mod common;
+
+use common::{bake, lit_room};
+use voxel_generator::validate::{validate_document, validate_glb};
+use voxel_generator::{bake_mesh, BakedMesh, MeshTexture, Palette, RadiosityColor, save_gltf, save_gltf_embedded, save_mesh, SaveMeshError, SolverOptions, voxel_to_mesh, SceneNode, Transform, VoxelGrid, write_glb, write_glb_scene, write_gltf};
+
+fn glb(mesh: &BakedMesh) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    write_glb(mesh, &mut bytes).unwrap();
+    bytes
+}
+
+fn glb_json(bytes: &[u8]) -> json::JsonValue {
+    let json_length = chunk_length(bytes, 12);
+    json::parse(std::str::from_utf8(&bytes[20..20 + json_length]).unwrap()).unwrap()
+}
+
+fn chunk_length(bytes: &[u8], offset: usize) -> usize {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap()) as usize
+}
+
+#[test]
+fn lightmapped_glb_is_valid() {
+    let errors = validate_glb(&glb(&bake(None)));
+    assert!(errors.is_empty(), "{:#?}", errors);
+}
+
+#[test]
+fn palette_glb_is_valid() {
+    let errors = validate_glb(&glb(&bake(Some(&Palette::default()))));
+    assert!(errors.is_empty(), "{:#?}", errors);
+}
+
+#[test]
+fn glb_chunks_are_padded_and_add_up() {
+    let bytes = glb(&bake(None));
+    assert_eq!(chunk_length(&bytes, 8), bytes.len());
+
+    let json_length = chunk_length(&bytes, 12);
+    assert_eq!(&bytes[16..20], b"JSON");
+    assert_eq!(json_length % 4, 0);
+
+    let bin_offset = 20 + json_length;
+    let bin_length = chunk_length(&bytes, bin_offset);
+    assert_eq!(&bytes[bin_offset + 4..bin_offset + 8], b"BIN\0");
+    assert_eq!(bin_length % 4, 0);
+    assert_eq!(bin_offset + 8 + bin_length, bytes.len());
+}
+
+#[test]
+fn buffer_views_are_aligned() {
+    let document = glb_json(&glb(&bake(None)));
+    assert!(document["bufferViews"].len() >= 5);
+    for view in document["bufferViews"].members() {
+        assert_eq!(view["byteOffset"].as_usize().unwrap() % 4, 0);
+    }
+}
+
+#[test]
+fn separated_gltf_is_valid() {
+    let mesh = bake(None);
+    let mut json = Vec::new();
+    let mut bin = Vec::new();
+    write_gltf(&mesh, "room", &mut json, &mut bin).unwrap();
+
+    let document = json::parse(std::str::from_utf8(&json).unwrap()).unwrap();
+    let errors = validate_document(&document, &[&bin]);
+    assert!(errors.is_empty(), "{:#?}", errors);
+}
+
+#[test]
+fn validator_catches_broken_files() {
+    let mut bytes = glb(&bake(None));
+    bytes[8] ^= 4;
+    assert!(!validate_glb(&bytes).is_empty());
+
+    let mut bytes = glb(&bake(None));
+    let last = bytes.len() - 1;
+    bytes.truncate(last);
+    assert!(!validate_glb(&bytes).is_empty());
+
+    assert!(!validate_glb(b"glTF").is_empty());
+}
+
+#[test]
+fn scene_has_a_named_node_per_mesh() {
+    let lit = bake(None);
+    let palette = Palette::default();
+    let first = bake(Some(&palette));
+    let second = bake(Some(&palette));
+    let shifted = Transform { translation: [1., 0., 0.], ..Transform::default() };
+    let nodes = [
+        SceneNode { name: "lit", mesh: &lit, transform: Transform::default(), ktx2: None },
+        SceneNode { name: "first", mesh: &first, transform: shifted, ktx2: None },
+        SceneNode { name: "second", mesh: &second, transform: Transform::default(), ktx2: None },
+    ];
+
+    let mut bytes = Vec::new();
+    write_glb_scene(&nodes, &mut bytes).unwrap();
+    let errors = validate_glb(&bytes);
+    assert!(errors.is_empty(), "{:#?}", errors);
+
+    let document = glb_json(&bytes);
+    let names: Vec<&str> = document["nodes"].members().map(|node| node["name"].as_str().unwrap()).collect();
+    assert_eq!(names, ["lit", "first", "second"]);
+    assert_eq!(document["meshes"].len(), 3);
+    assert!(document["nodes"][0]["translation"].is_null());
+    assert_eq!(document["nodes"][1]["translation"][0].as_f32(), Some(1.));
+
+    // The palette meshes share the colormap, but each has its own light and emissive texture
+    let base_texture = |mesh: usize| {
+        let material = document["meshes"][mesh]["primitives"][0]["material"].as_usize().unwrap();
+        document["materials"][material]["pbrMetallicRoughness"]["baseColorTexture"]["index"].clone()
+    };
+    assert_eq!(base_texture(1), base_texture(2));
+    assert_ne!(base_texture(0), base_texture(1));
+    assert_eq!(document["images"].len(), 7);
+    assert_eq!(document["materials"].len(), 3);
+    assert_eq!(document["samplers"].len(), 1);
+}
+
+#[test]
+fn emitters_get_an_emissive_texture() {
+    let document = glb_json(&glb(&bake(None)));
+    let material = &document["materials"][0];
+    assert!(material["emissiveTexture"]["index"].as_usize().is_some());
+    assert_eq!(material["emissiveFactor"][0].as_f32(), Some(1.));
+    assert!(document["extensionsUsed"].is_null());
+}
+
+#[test]
+fn bright_emitters_use_emissive_strength() {
+    let mut voxels = lit_room();
+    voxels[1][2][1].emission = 3.;
+    let options = SolverOptions { iterations: 1, ..SolverOptions::default() };
+    let bytes = glb(&bake_mesh(voxel_to_mesh(&voxels, None), &options));
+    let errors = validate_glb(&bytes);
+    assert!(errors.is_empty(), "{:#?}", errors);
+
+    let document = glb_json(&bytes);
+    assert_eq!(document["materials"][0]["extensions"]["KHR_materials_emissive_strength"]["emissiveStrength"].as_f32(), Some(3.));
+    assert!(document["extensionsUsed"].members().any(|used| used == "KHR_materials_emissive_strength"));
+}
+
+#[test]
+fn unlit_materials_list_the_extension() {
+    let mut mesh = bake(None);
+    mesh.unlit = true;
+    let bytes = glb(&mesh);
+    let errors = validate_glb(&bytes);
+    assert!(errors.is_empty(), "{:#?}", errors);
+
+    let document = glb_json(&bytes);
+    assert!(document["materials"][0]["extensions"]["KHR_materials_unlit"].is_object());
+    assert!(document["extensionsUsed"].members().any(|used| used == "KHR_materials_unlit"));
+}
+
+#[test]
+fn lightmap_is_on_the_second_uv_set() {
+    let document = glb_json(&glb(&bake(Some(&Palette::default()))));
+    let attributes = &document["meshes"][0]["primitives"][0]["attributes"];
+    assert!(attributes["TEXCOORD_0"].as_usize().is_some());
+    assert!(attributes["TEXCOORD_1"].as_usize().is_some());
+    assert_ne!(attributes["TEXCOORD_0"], attributes["TEXCOORD_1"]);
+
+    let material = &document["materials"][0];
+    assert_eq!(material["pbrMetallicRoughness"]["baseColorTexture"]["texCoord"].as_usize(), Some(0));
+    assert_eq!(material["extras"]["lightmapTexture"]["texCoord"].as_usize(), Some(1));
+    assert_eq!(material["emissiveTexture"]["texCoord"].as_usize(), Some(1));
+}
+
+#[test]
+fn large_meshes_get_a_larger_lightmap() {
+    // Every voxel of a checkerboard shows all its sides inside the grid
+    let voxels: VoxelGrid<16> = std::array::from_fn(|x| std::array::from_fn(|y| std::array::from_fn(|z| {
+        let alpha = if (x + y + z) % 2 == 0 { 255 } else { 0 };
+        RadiosityColor { color: [128, 128, 128, alpha].into(), emission: 0. }
+    })));
+    let mesh = voxel_to_mesh(&voxels, None);
+    assert!(mesh.faces.len() > 64 * 64, "{} faces", mesh.faces.len());
+    assert!(mesh.lightmap_coordinates.iter().all(|position| (0. ..1.).contains(&position.x) && (0. ..1.).contains(&position.y)));
+
+    let baked = bake_mesh(mesh, &SolverOptions { subdivisions: 2, lighting: false, ..SolverOptions::default() });
+    match &baked.texture {
+        MeshTexture::Embedded(image) => assert_eq!(image.to_rgba8().dimensions(), (128 << 2, 128 << 2)),
+        MeshTexture::External(uri) => panic!("expected a lightmap, got {}", uri),
+    }
+    let errors = validate_glb(&glb(&baked));
+    assert!(errors.is_empty(), "{:#?}", errors);
+}
+
+#[test]
+fn saving_writes_only_files_that_load_on_their_own() {
+    let directory = std::env::temp_dir().join(format!("voxel_generator_glb_{}", std::process::id()));
+    save_mesh(&bake(None), "room", Transform::default(), &directory.join("room")).unwrap();
+
+    let mut names: Vec<String> = std::fs::read_dir(&directory).unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    assert_eq!(names, ["room.glb", "room.png", "room_emissive.png"]);
+    assert!(validate_glb(&std::fs::read(directory.join("room.glb")).unwrap()).is_empty());
+    std::fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn empty_grids_are_not_exported() {
+    let voxels: VoxelGrid<4> = [[[RadiosityColor { color: [0, 0, 0, 0].into(), emission: 0. }; 4]; 4]; 4];
+    let baked = bake_mesh(voxel_to_mesh(&voxels, None), &SolverOptions { lighting: false, ..SolverOptions::default() });
+    assert!(baked.mesh.faces.is_empty());
+    assert!(matches!(write_glb(&baked, &mut Vec::new()), Err(SaveMeshError::EmptyMeshError(_))));
+
+    let directory = std::env::temp_dir().join(format!("voxel_generator_empty_{}", std::process::id()));
+    assert!(save_gltf(&baked, "empty", Transform::default(), &directory.join("empty")).is_err());
+    assert_eq!(std::fs::read_dir(&directory).unwrap().count(), 0);
+    std::fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn single_files_name_and_place_their_node() {
+    let directory = std::env::temp_dir().join(format!("voxel_generator_node_{}", std::process::id()));
+    let transform = Transform { translation: [2., 0., -1.], ..Transform::default() };
+    save_gltf_embedded(&bake(None), "hallway_curve_left", transform, &directory.join("tile")).unwrap();
+
+    let document = json::parse(&std::fs::read_to_string(directory.join("tile.gltf")).unwrap()).unwrap();
+    assert_eq!(document["nodes"].len(), 1);
+    assert_eq!(document["nodes"][0]["name"], "hallway_curve_left");
+    assert_eq!(document["nodes"][0]["translation"], json::array![2., 0., -1.]);
+    std::fs::remove_dir_all(directory).unwrap();
+}
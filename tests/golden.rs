@@ -0,0 +1,230 @@
+//! Bakes the bundled tiles with fixed settings, and one with the settings of `scene.json`, and
+//! compares the result with the files in `tests/golden`, so changes to the solver or the
+//! exporter can't alter them unnoticed.
+//!
+//! After an intended change, run `UPDATE_GOLDEN=1 cargo test --test golden` to write the new
+//! golden files, and check the differences before committing them.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use image::RgbaImage;
+use json::{array, object, JsonValue};
+use voxel_generator::scene::{bake_tile, parse_scene};
+use voxel_generator::{BakedMesh, MeshTexture, write_glb};
+
+/// Fewer iterations than the shipped scene, so the tests stay quick.
+const SCENE: &str = r#"{
+    "solver": {"iterations": 2, "subdivisions": 0},
+    "layers": {
+        "hallway": {"edge": "media/hallway_edge.png", "end": "media/hallway_edge_end.png"},
+        "room": {"edge": "media/room_edge.png", "end": "media/room_edge_end.png"}
+    },
+    "tiles": [
+        {"name": "hallway", "layers": "hallway", "shape": "straight", "brightness": 0.4},
+        {"name": "hallway_curve_left", "layers": "hallway", "shape": "left_curve", "brightness": 0.4},
+        {"name": "hallway_curve_right", "layers": "hallway", "shape": "right_curve", "brightness": 0.4},
+        {"name": "room", "layers": "room", "shape": "straight", "brightness": 2.0},
+        {"name": "room_curve_left", "layers": "room", "shape": "left_curve", "brightness": 2.0},
+        {"name": "room_curve_right", "layers": "room", "shape": "right_curve", "brightness": 2.0}
+    ]
+}"#;
+
+/// How far a channel of a lightmap pixel may be off, to allow for floating point differences
+/// between platforms.
+const PIXEL_TOLERANCE: u8 = 2;
+
+fn golden_directory() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden")
+}
+
+/// Bakes a tile of a scene with its own solver settings.
+fn bake(source: &str, name: &str) -> BakedMesh {
+    let scene = parse_scene(source, Path::new(env!("CARGO_MANIFEST_DIR"))).unwrap();
+    let tile = scene.tiles.iter().find(|tile| tile.name == name).unwrap();
+    bake_tile(&scene, tile)
+}
+
+/// The bundled `scene.json`, with the solver settings the tiles are shipped with.
+fn shipped_scene() -> String {
+    fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join("scene.json")).unwrap()
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> usize {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap()) as usize
+}
+
+/// The values of an accessor, one list of components per element.
+fn accessor_values(document: &JsonValue, bin: &[u8], accessor: &JsonValue) -> Vec<Vec<f64>> {
+    let view = &document["bufferViews"][accessor["bufferView"].as_usize().unwrap()];
+    let components = match accessor["type"].as_str().unwrap() {
+        "SCALAR" => 1,
+        "VEC2" => 2,
+        "VEC3" => 3,
+        "VEC4" => 4,
+        other => panic!("unexpected accessor type {}", other),
+    };
+    let start = view["byteOffset"].as_usize().unwrap_or(0) + accessor["byteOffset"].as_usize().unwrap_or(0);
+    let stride = view["byteStride"].as_usize().unwrap_or(components * 4);
+    (0..accessor["count"].as_usize().unwrap()).map(|element| {
+        (0..components).map(|component| {
+            let offset = start + element * stride + component * 4;
+            let bytes: [u8; 4] = bin[offset..offset + 4].try_into().unwrap();
+            match accessor["componentType"].as_u32().unwrap() {
+                5126 => f32::from_le_bytes(bytes) as f64,
+                5125 => u32::from_le_bytes(bytes) as f64,
+                other => panic!("unexpected component type {}", other),
+            }
+        }).collect()
+    }).collect()
+}
+
+/// FNV-1a over the values, for data that has to match exactly.
+fn checksum(values: &[Vec<f64>]) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for value in values.iter().flatten() {
+        for byte in (*value as u64).to_le_bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    format!("{:016x}", hash)
+}
+
+/// The count, range and mean of every component of an accessor, and for the indices a checksum
+/// as well.
+fn accessor_summary(name: &str, values: &[Vec<f64>]) -> JsonValue {
+    let components = values.first().map_or(0, |value| value.len());
+    let column = |i: usize| values.iter().map(move |value| value[i]);
+    let mut summary = object!{
+        "name" => name,
+        "count" => values.len(),
+        "min" => (0..components).map(|i| column(i).fold(f64::INFINITY, f64::min)).collect::<Vec<f64>>(),
+        "max" => (0..components).map(|i| column(i).fold(f64::NEG_INFINITY, f64::max)).collect::<Vec<f64>>(),
+        "mean" => (0..components).map(|i| column(i).sum::<f64>() / values.len().max(1) as f64).collect::<Vec<f64>>()
+    };
+    if name == "indices" {
+        summary["checksum"] = checksum(values).into();
+    }
+    summary
+}
+
+/// The statistics of a baked mesh and of the accessors it is exported with.
+fn summary(mesh: &BakedMesh) -> JsonValue {
+    let mut bytes = Vec::new();
+    write_glb(mesh, &mut bytes).unwrap();
+    let json_length = read_u32(&bytes, 12);
+    let document = json::parse(std::str::from_utf8(&bytes[20..20 + json_length]).unwrap()).unwrap();
+    let bin = &bytes[20 + json_length + 8..];
+
+    let primitive = &document["meshes"][0]["primitives"][0];
+    let mut accessors = array![];
+    for (name, accessor) in primitive["attributes"].entries() {
+        let values = accessor_values(&document, bin, &document["accessors"][accessor.as_usize().unwrap()]);
+        accessors.push(accessor_summary(name, &values)).unwrap();
+    }
+    let indices = accessor_values(&document, bin, &document["accessors"][primitive["indices"].as_usize().unwrap()]);
+    accessors.push(accessor_summary("indices", &indices)).unwrap();
+
+    object!{
+        "vertices" => mesh.mesh.positions.len(),
+        "triangles" => mesh.mesh.indices.len() / 3,
+        "faces" => mesh.mesh.faces.len(),
+        "lit_faces" => mesh.lit_faces.len(),
+        "accessors" => accessors
+    }
+}
+
+fn lightmap(mesh: &BakedMesh) -> RgbaImage {
+    match &mesh.texture {
+        MeshTexture::Embedded(image) => image.to_rgba8(),
+        MeshTexture::External(uri) => panic!("expected a lightmap, got {}", uri),
+    }
+}
+
+/// Whether two numbers from a summary are the same, allowing for rounding.
+fn close(actual: f64, expected: f64) -> bool {
+    (actual - expected).abs() <= 1e-5 + 1e-4 * expected.abs()
+}
+
+/// Lists where `actual` differs from `expected`, comparing numbers with [`close`].
+fn differences(path: &str, actual: &JsonValue, expected: &JsonValue, found: &mut Vec<String>) {
+    match (actual, expected) {
+        (JsonValue::Object(_), JsonValue::Object(_)) => {
+            for (key, value) in expected.entries() {
+                differences(&format!("{}.{}", path, key), &actual[key], value, found);
+            }
+        }
+        (JsonValue::Array(actual_members), JsonValue::Array(expected_members)) if actual_members.len() == expected_members.len() => {
+            for (i, (actual, expected)) in actual_members.iter().zip(expected_members).enumerate() {
+                // Accessors are easier to find by their name than their position
+                let name = expected["name"].as_str().map_or(i.to_string(), str::to_string);
+                differences(&format!("{}[{}]", path, name), actual, expected, found);
+            }
+        }
+        _ => {
+            let same = match (actual.as_f64(), expected.as_f64()) {
+                (Some(actual), Some(expected)) => close(actual, expected),
+                _ => actual == expected,
+            };
+            if !same {
+                found.push(format!("{}: {} instead of {}", path, actual, expected));
+            }
+        }
+    }
+}
+
+/// Compares a baked mesh with the golden files called `name`.
+fn check_golden(name: &str, mesh: &BakedMesh) {
+    let summary = summary(mesh);
+    let lightmap = lightmap(mesh);
+    let summary_path = golden_directory().join(format!("{}.json", name));
+    let lightmap_path = golden_directory().join(format!("{}.png", name));
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::create_dir_all(golden_directory()).unwrap();
+        fs::write(&summary_path, json::stringify_pretty(summary, 2) + "\n").unwrap();
+        lightmap.save(&lightmap_path).unwrap();
+        return;
+    }
+
+    let source = fs::read_to_string(&summary_path)
+        .unwrap_or_else(|_| panic!("{} is missing, run with UPDATE_GOLDEN=1 to write it", summary_path.display()));
+    let mut found = Vec::new();
+    differences(name, &summary, &json::parse(&source).unwrap(), &mut found);
+    assert!(found.is_empty(), "{} differs from {}:\n{}", name, summary_path.display(), found.join("\n"));
+
+    let expected = image::open(&lightmap_path).unwrap().to_rgba8();
+    assert_eq!(lightmap.dimensions(), expected.dimensions());
+    let off: Vec<(u32, u32)> = lightmap.enumerate_pixels()
+        .filter(|(x, y, pixel)| pixel.0.iter().zip(expected.get_pixel(*x, *y).0).any(|(a, b)| a.abs_diff(b) > PIXEL_TOLERANCE))
+        .map(|(x, y, _)| (x, y))
+        .collect();
+    assert!(off.is_empty(), "{} lightmap pixels differ from {}, first at {:?}", off.len(), lightmap_path.display(), off.first());
+}
+
+#[test]
+fn hallway_matches_golden() {
+    check_golden("hallway", &bake(SCENE, "hallway"));
+}
+
+#[test]
+fn hallway_curves_match_golden() {
+    check_golden("hallway_curve_left", &bake(SCENE, "hallway_curve_left"));
+    check_golden("hallway_curve_right", &bake(SCENE, "hallway_curve_right"));
+}
+
+#[test]
+fn room_matches_golden() {
+    check_golden("room", &bake(SCENE, "room"));
+}
+
+#[test]
+fn room_curves_match_golden() {
+    check_golden("room_curve_left", &bake(SCENE, "room_curve_left"));
+    check_golden("room_curve_right", &bake(SCENE, "room_curve_right"));
+}
+
+#[test]
+fn shipped_hallway_matches_golden() {
+    check_golden("shipped_hallway", &bake(&shipped_scene(), "hallway"));
+}
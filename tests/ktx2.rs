@@ -0,0 +1,160 @@
+mod common;
+
+use common::bake;
+use std::path::Path;
+use image::{Rgba, RgbaImage};
+use voxel_generator::validate::{validate_document, validate_glb};
+use voxel_generator::voxel::lightmap_mip_levels;
+use voxel_generator::scene::parse_scene;
+use voxel_generator::{Ktx2Format, MeshTexture, write_glb, write_gltf, write_ktx2};
+
+fn ktx2(levels: &[RgbaImage], format: Ktx2Format) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    write_ktx2(levels, format, &mut bytes).unwrap();
+    bytes
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> usize {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap()) as usize
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> usize {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap()) as usize
+}
+
+/// Offset and length of every level in the level index.
+fn levels(bytes: &[u8]) -> Vec<(usize, usize)> {
+    (0..read_u32(bytes, 40)).map(|level| (read_u64(bytes, 80 + level * 24), read_u64(bytes, 88 + level * 24))).collect()
+}
+
+fn gradient(size: u32) -> RgbaImage {
+    RgbaImage::from_fn(size, size, |x, y| Rgba([(x * 255 / size) as u8, (y * 255 / size) as u8, 128, 255]))
+}
+
+#[test]
+fn header_describes_the_image() {
+    let bytes = ktx2(&[gradient(16)], Ktx2Format::Rgba8);
+    assert_eq!(&bytes[0..12], &[0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A]);
+    assert_eq!(read_u32(&bytes, 12), 43); // VK_FORMAT_R8G8B8A8_SRGB
+    assert_eq!(read_u32(&bytes, 20), 16);
+    assert_eq!(read_u32(&bytes, 24), 16);
+    assert_eq!(read_u32(&bytes, 36), 1); // Faces
+
+    let (descriptor_offset, descriptor_length) = (read_u32(&bytes, 48), read_u32(&bytes, 52));
+    assert_eq!(descriptor_offset, 80 + 24);
+    assert_eq!(read_u32(&bytes, descriptor_offset), descriptor_length);
+    assert_eq!(descriptor_length, 4 + 24 + 4 * 16);
+}
+
+#[test]
+fn uncompressed_data_is_the_image() {
+    let image = gradient(8);
+    let bytes = ktx2(std::slice::from_ref(&image), Ktx2Format::Rgba8);
+    let (offset, length) = levels(&bytes)[0];
+    assert_eq!(offset % 4, 0);
+    assert_eq!(offset + length, bytes.len());
+    assert_eq!(&bytes[offset..offset + length], image.as_raw().as_slice());
+}
+
+#[test]
+fn levels_are_stored_smallest_first_and_aligned() {
+    let bytes = ktx2(&[gradient(16), gradient(8), gradient(4)], Ktx2Format::Bc1);
+    let levels = levels(&bytes);
+    assert_eq!(levels.len(), 3);
+    assert_eq!(levels.iter().map(|level| level.1).collect::<Vec<usize>>(), [16 * 8, 4 * 8, 8]);
+    assert!(levels.iter().all(|level| level.0 % 8 == 0));
+    assert!(levels[2].0 < levels[1].0 && levels[1].0 < levels[0].0);
+    assert_eq!(levels[0].0 + levels[0].1, bytes.len());
+}
+
+#[test]
+fn solid_blocks_compress_exactly() {
+    let bytes = ktx2(&[RgbaImage::from_pixel(4, 4, Rgba([255, 0, 0, 255]))], Ktx2Format::Bc1);
+    assert_eq!(read_u32(&bytes, 12), 132); // VK_FORMAT_BC1_RGB_SRGB_BLOCK
+    let (offset, length) = levels(&bytes)[0];
+    assert_eq!(length, 8);
+    let block = &bytes[offset..offset + 8];
+    assert_eq!(u16::from_le_bytes([block[0], block[1]]), 0xF800);
+    assert_eq!(u16::from_le_bytes([block[2], block[3]]), 0xF800);
+    assert_eq!(&block[4..8], &[0, 0, 0, 0]);
+}
+
+#[test]
+fn partial_blocks_are_padded() {
+    let bytes = ktx2(&[gradient(6)], Ktx2Format::Bc1);
+    assert_eq!(levels(&bytes)[0].1, 2 * 2 * 8);
+}
+
+#[test]
+fn mip_levels_stop_at_one_pixel_per_face() {
+    // Two faces of 4x4 pixels next to each other, and an unused one
+    let image = RgbaImage::from_fn(8, 8, |x, y| match (x / 4, y / 4) {
+        (0, _) => Rgba([200, 0, 0, 255]),
+        (1, 0) => Rgba([0, 100 + (x % 2) as u8 * 50, 0, 255]),
+        _ => Rgba([0, 0, 0, 0]),
+    });
+    let levels = lightmap_mip_levels(&image, 2);
+    assert_eq!(levels.iter().map(|level| level.width()).collect::<Vec<u32>>(), [8, 4, 2]);
+
+    let last = &levels[2];
+    assert_eq!(*last.get_pixel(0, 0), Rgba([200, 0, 0, 255]));
+    assert_eq!(*last.get_pixel(0, 1), Rgba([200, 0, 0, 255]));
+    assert_eq!(*last.get_pixel(1, 0), Rgba([0, 125, 0, 255]));
+    assert_eq!(*last.get_pixel(1, 1), Rgba([0, 0, 0, 0]));
+}
+
+#[test]
+fn mipmapped_textures_sample_the_ktx2_files() {
+    let mut mesh = bake(None);
+    mesh.mipmaps = true;
+    let (mut json, mut bin) = (Vec::new(), Vec::new());
+    write_gltf(&mesh, "room", &mut json, &mut bin).unwrap();
+    let document = json::parse(std::str::from_utf8(&json).unwrap()).unwrap();
+    let errors = validate_document(&document, &[&bin]);
+    assert!(errors.is_empty(), "{:#?}", errors);
+
+    // The PNG stays the source for viewers without KTX2 support
+    let uris: Vec<&str> = document["textures"].members().map(|texture| {
+        let image = &document["images"][texture["extensions"]["KHR_texture_basisu"]["source"].as_usize().unwrap()];
+        assert_eq!(image["mimeType"], "image/ktx2");
+        assert_eq!(document["images"][texture["source"].as_usize().unwrap()]["mimeType"], "image/png");
+
+        let sampler = &document["samplers"][texture["sampler"].as_usize().unwrap()];
+        assert_eq!((sampler["magFilter"].as_u32(), sampler["minFilter"].as_u32()), (Some(9728), Some(9986)));
+        image["uri"].as_str().unwrap()
+    }).collect();
+    assert_eq!(uris, ["room.ktx2", "room_emissive.ktx2"]);
+    assert!(document["extensionsUsed"].members().any(|used| used == "KHR_texture_basisu"));
+
+    let texture = match &mesh.texture {
+        MeshTexture::Embedded(texture) => texture,
+        MeshTexture::External(_) => panic!("expected a lightmap"),
+    };
+    assert_eq!(levels(&ktx2(&mesh.mip_levels(texture), Ktx2Format::Rgba8)).len(), 2);
+
+    // Without subdivisions every face is a single pixel already
+    mesh.subdivisions = 0;
+    assert_eq!(mesh.mip_levels(texture).len(), 1);
+}
+
+#[test]
+fn textures_without_mip_maps_only_use_the_png() {
+    let mesh = bake(None);
+    let mut bytes = Vec::new();
+    write_glb(&mesh, &mut bytes).unwrap();
+    assert!(validate_glb(&bytes).is_empty());
+
+    let json_length = u32::from_le_bytes(bytes[12..16].try_into().unwrap()) as usize;
+    let document = json::parse(std::str::from_utf8(&bytes[20..20 + json_length]).unwrap()).unwrap();
+    assert!(document["extensionsUsed"].members().all(|used| used != "KHR_texture_basisu"));
+    assert!(document["samplers"].members().all(|sampler| sampler["minFilter"].as_u32() == Some(9728)));
+}
+
+#[test]
+fn mip_maps_write_uncompressed_ktx2_files_by_default() {
+    let scene = parse_scene(r#"{"mipmaps": true, "layers": {}, "tiles": []}"#, Path::new("")).unwrap();
+    assert_eq!(scene.ktx2_format(), Some(Ktx2Format::Rgba8));
+
+    let scene = parse_scene(r#"{"mipmaps": true, "ktx2": "bc1", "layers": {}, "tiles": []}"#, Path::new("")).unwrap();
+    assert_eq!(scene.ktx2_format(), Some(Ktx2Format::Bc1));
+}
@@ -0,0 +1,141 @@
+mod common;
+
+use common::{bake, lit_room};
+use voxel_generator::radiosity::apply_lights;
+use voxel_generator::vector::Vec3;
+use voxel_generator::{bake_mesh, BakedMesh, Light, RadiosityColor, Sky, SolverOptions, Sun, VoxelGrid, voxel_to_mesh};
+
+fn bake_with(lights: Vec<Light>) -> BakedMesh {
+    let options = SolverOptions { iterations: 1, lights, ..SolverOptions::default() };
+    bake_mesh(voxel_to_mesh(&lit_room(), None), &options)
+}
+
+/// The summed brightness of the faces on top of the floor, which lies in the Z plane.
+fn floor_brightness(mesh: &BakedMesh) -> f32 {
+    mesh.lit_faces.iter()
+        .filter(|face| face.normal.z < 0. && face.corners[0].z == 0.25)
+        .map(|face| face.brightness.iter().sum::<f32>())
+        .sum()
+}
+
+fn above_the_floor() -> Vec3 {
+    Vec3 { x: 0.6, y: 0.6, z: 0.9 }
+}
+
+#[test]
+fn point_lights_brighten_the_floor() {
+    let unlit = floor_brightness(&bake(None));
+    let lit = floor_brightness(&bake_with(vec![Light::Point { position: above_the_floor(), color: [0.1; 3] }]));
+    assert!(lit > unlit);
+}
+
+#[test]
+fn point_lights_follow_the_colour_of_the_light() {
+    let mesh = bake_with(vec![Light::Point { position: above_the_floor(), color: [0.1, 0., 0.] }]);
+    let reference = bake(None);
+    let red: f32 = mesh.lit_faces.iter().map(|face| face.brightness[0]).sum();
+    let blue: f32 = mesh.lit_faces.iter().map(|face| face.brightness[2]).sum();
+    let reference_blue: f32 = reference.lit_faces.iter().map(|face| face.brightness[2]).sum();
+    assert!(red > blue);
+    assert!((blue - reference_blue).abs() < 1e-4);
+}
+
+#[test]
+fn spot_lights_only_light_inside_their_cone() {
+    let unlit = floor_brightness(&bake(None));
+    let spot = |direction| Light::Spot {
+        position: above_the_floor(),
+        direction,
+        inner_angle: 0.3,
+        outer_angle: 0.5,
+        color: [0.1; 3],
+    };
+    let down = floor_brightness(&bake_with(vec![spot(Vec3 { x: 0., y: 0., z: -1. })]));
+    let up = floor_brightness(&bake_with(vec![spot(Vec3 { x: 0., y: 0., z: 1. })]));
+    assert!(down > unlit);
+    assert!((up - unlit).abs() < 1e-4);
+}
+
+#[test]
+fn area_lights_shine_from_one_side() {
+    let unlit = floor_brightness(&bake(None));
+    let x = Vec3 { x: 0.25, y: 0., z: 0. };
+    let y = Vec3 { x: 0., y: 0.25, z: 0. };
+    let area = |edges| Light::Area { corner: above_the_floor(), edges, color: [0.5; 3] };
+    let down = floor_brightness(&bake_with(vec![area([y, x])]));
+    let up = floor_brightness(&bake_with(vec![area([x, y])]));
+    assert!(down > unlit);
+    assert!((up - unlit).abs() < 1e-4);
+}
+
+#[test]
+fn voxels_between_a_light_and_a_face_cast_a_shadow() {
+    let mesh = voxel_to_mesh(&lit_room(), None);
+    let mut faces = mesh.faces.clone();
+    // Low down on the -X side of the emitting voxel, which stands between it and the floor on the +X side
+    let light = Light::Point { position: Vec3 { x: 0.15, y: 0.625, z: 0.3 }, color: [0.1; 3] };
+    apply_lights(&mut faces, &mesh.faces, &[light]);
+    let gain = |x: f32| {
+        let i = mesh.faces.iter().position(|face| {
+            face.normal.z < 0. && face.corners[0].z == 0.25 && face.corners[0].x == x && face.corners[0].y == 0.5
+        }).unwrap();
+        faces[i].brightness[0] - mesh.faces[i].brightness[0]
+    };
+    assert!(gain(0.) > 0.);
+    assert_eq!(gain(0.5), 0.);
+}
+
+/// How much brighter each floor face got than without the environment, with whether it lies
+/// right next to the emitting voxel on the -X side.
+fn floor_gain(voxels: &VoxelGrid<4>, options: SolverOptions) -> Vec<(bool, f32)> {
+    let mesh = bake_mesh(voxel_to_mesh(voxels, None), &SolverOptions { iterations: 1, ..options });
+    let reference = bake_mesh(voxel_to_mesh(voxels, None), &SolverOptions { iterations: 1, ..SolverOptions::default() });
+    mesh.lit_faces.iter().zip(&reference.lit_faces)
+        .filter(|(face, _)| face.normal.z < 0. && face.corners[0].z == 0.25)
+        .map(|(face, reference)| {
+            let center = face.corners.iter().fold(Vec3 { x: 0., y: 0., z: 0. }, |sum, corner| sum + *corner * 0.25);
+            let beside = (0.0..0.25).contains(&center.x) && (0.5..0.75).contains(&center.y);
+            (beside, face.brightness[0] - reference.brightness[0])
+        })
+        .collect()
+}
+
+#[test]
+fn the_sun_casts_shadows() {
+    let sun = Sun { direction: Vec3 { x: -1., y: 0., z: -1. }, color: [1.; 3] };
+    let gain = floor_gain(&lit_room(), SolverOptions { sun: Some(sun), ..SolverOptions::default() });
+    assert!(gain.iter().any(|(beside, _)| *beside));
+    for (beside, gain) in gain {
+        if beside {
+            assert!(gain.abs() < 1e-4, "{}", gain);
+        } else {
+            assert!(gain > 0.1, "{}", gain);
+        }
+    }
+}
+
+#[test]
+fn the_neighbouring_tiles_cast_shadows_too() {
+    // A black voxel instead of the emitting one, so none of the sun is reflected off its side
+    let mut voxels = lit_room();
+    voxels[1][2][1] = RadiosityColor { color: [0, 0, 0, 255].into(), emission: 0. };
+    // Low from -X, so the shadow of the voxel mirrored across X = 0 reaches the floor beside it
+    let sun = Sun { direction: Vec3 { x: 1., y: 0., z: -0.25 }, color: [1.; 3] };
+    let gain = floor_gain(&voxels, SolverOptions { sun: Some(sun), ..SolverOptions::default() });
+    assert!(gain.iter().any(|(beside, _)| *beside));
+    assert!(gain.iter().any(|(_, gain)| *gain > 0.01));
+    for (beside, gain) in gain {
+        if beside {
+            assert!(gain.abs() < 1e-4, "{}", gain);
+        }
+    }
+}
+
+#[test]
+fn the_sky_blends_to_the_horizon() {
+    let uniform = floor_gain(&lit_room(), SolverOptions { sky: Some(Sky { zenith: [1.; 3], horizon: [1.; 3] }), ..SolverOptions::default() });
+    let gradient = floor_gain(&lit_room(), SolverOptions { sky: Some(Sky { zenith: [1.; 3], horizon: [0.; 3] }), ..SolverOptions::default() });
+    for ((_, uniform), (_, gradient)) in uniform.iter().zip(&gradient) {
+        assert!(*gradient > 0. && gradient < uniform);
+    }
+}
@@ -0,0 +1,48 @@
+mod common;
+
+use common::bake;
+use voxel_generator::{Palette, write_mtl, write_obj};
+
+fn obj(palette: Option<&Palette>) -> String {
+    let mut bytes = Vec::new();
+    write_obj(&bake(palette), "room.mtl", "room", &mut bytes).unwrap();
+    String::from_utf8(bytes).unwrap()
+}
+
+fn lines<'a>(obj: &'a str, prefix: &'a str) -> impl Iterator<Item = Vec<&'a str>> + 'a {
+    obj.lines().filter(move |line| line.split(' ').next() == Some(prefix)).map(|line| line.split(' ').skip(1).collect())
+}
+
+#[test]
+fn faces_reference_existing_vertices() {
+    let obj = obj(None);
+    let vertices = lines(&obj, "v").count();
+    assert_eq!(lines(&obj, "vt").count(), vertices);
+    assert_eq!(lines(&obj, "vn").count(), vertices);
+    assert!(vertices > 0);
+
+    for face in lines(&obj, "f") {
+        assert_eq!(face.len(), 3);
+        for corner in face {
+            for index in corner.split('/') {
+                let index: usize = index.parse().unwrap();
+                assert!((1..=vertices).contains(&index));
+            }
+        }
+    }
+}
+
+#[test]
+fn palette_colours_follow_positions() {
+    let obj = obj(Some(&Palette::default()));
+    assert!(lines(&obj, "v").all(|vertex| vertex.len() == 6));
+}
+
+#[test]
+fn material_uses_texture() {
+    let mut bytes = Vec::new();
+    write_mtl("room", "room.png", &mut bytes).unwrap();
+    let mtl = String::from_utf8(bytes).unwrap();
+    assert!(mtl.contains("newmtl room\n"));
+    assert!(mtl.contains("map_Kd room.png\n"));
+}
@@ -0,0 +1,102 @@
+mod common;
+
+use common::{bake, lit_room};
+use voxel_generator::radiosity::ambient_occlusion;
+use voxel_generator::validate::validate_glb;
+use voxel_generator::{AmbientOcclusion, bake_mesh, BakedMesh, RadiosityColor, SolverOptions, voxel_to_mesh, write_glb};
+
+fn bake_occlusion(multiply: bool, lighting: bool) -> BakedMesh {
+    let options = SolverOptions {
+        iterations: 1,
+        lighting,
+        ambient_occlusion: Some(AmbientOcclusion { distance: 0.25, multiply }),
+        ..SolverOptions::default()
+    };
+    bake_mesh(voxel_to_mesh(&lit_room(), None), &options)
+}
+
+fn document(mesh: &BakedMesh) -> json::JsonValue {
+    let mut bytes = Vec::new();
+    write_glb(mesh, &mut bytes).unwrap();
+    assert_eq!(validate_glb(&bytes), Vec::<String>::new());
+    let json_length = u32::from_le_bytes(bytes[12..16].try_into().unwrap()) as usize;
+    json::parse(std::str::from_utf8(&bytes[20..20 + json_length]).unwrap()).unwrap()
+}
+
+#[test]
+fn faces_next_to_voxels_are_occluded() {
+    let mesh = voxel_to_mesh(&lit_room(), None);
+    let open = ambient_occlusion(&mesh.faces, &mesh.faces, 0.25);
+    let floor = |x: f32, y: f32| mesh.faces.iter().position(|face| {
+        face.normal.z < 0. && face.corners[0].z == 0.25 && face.corners[0].x == x && face.corners[0].y == y
+    }).unwrap();
+    // Beside the emitting voxel, and in the far corner
+    assert!(open[floor(0., 0.5)] < 0.9);
+    assert_eq!(open[floor(0.75, 0.)], 1.);
+    assert!(open.iter().all(|open| (0. ..=1.).contains(open)));
+}
+
+#[test]
+fn occlusion_is_exported_as_a_texture_of_its_own() {
+    let mesh = bake_occlusion(false, true);
+    assert!(mesh.occlusion.is_some());
+    let document = document(&mesh);
+    let material = &document["materials"][0];
+    assert_eq!(material["occlusionTexture"]["texCoord"].as_u32(), Some(1));
+    assert_ne!(material["occlusionTexture"]["index"], material["pbrMetallicRoughness"]["baseColorTexture"]["index"]);
+}
+
+#[test]
+fn multiplied_occlusion_darkens_the_lightmap() {
+    let mesh = bake_occlusion(true, true);
+    assert!(mesh.occlusion.is_none());
+    assert!(document(&mesh)["materials"][0]["occlusionTexture"].is_null());
+
+    let reference = bake(None);
+    let darker = mesh.lit_faces.iter().zip(&reference.lit_faces).filter(|(face, reference)| face.brightness[0] < reference.brightness[0]).count();
+    assert!(darker > 0);
+    assert!(mesh.lit_faces.iter().zip(&reference.lit_faces).all(|(face, reference)| face.brightness[0] <= reference.brightness[0]));
+}
+
+#[test]
+fn occlusion_works_without_lighting() {
+    let mesh = bake_occlusion(true, false);
+    // The floor keeps its grey colour where nothing is around it
+    assert!(mesh.lit_faces.iter().any(|face| (face.brightness[0] - 0.5).abs() < 1e-6));
+    assert!(mesh.lit_faces.iter().any(|face| face.brightness[0] < 0.45 && face.brightness[0] > 0.));
+}
+
+#[test]
+fn mirrored_copies_occlude_faces_next_to_the_mirror_planes() {
+    let empty = RadiosityColor { color: [0, 0, 0, 0].into(), emission: 0. };
+    let grey = RadiosityColor { color: [128, 128, 128, 255].into(), emission: 0. };
+    let mut voxels = [[[empty; 8]; 8]; 8];
+    for plane in voxels.iter_mut() {
+        plane[0] = [grey; 8];
+    }
+    // The pillar beside the first column is mirrored across X=0 to two columns on its other
+    // side, like the two pillars around the sixth column
+    voxels[1][1][4] = grey;
+    voxels[3][1][4] = grey;
+    voxels[6][1][4] = grey;
+
+    let options = SolverOptions {
+        subdivisions: 0,
+        lighting: false,
+        ambient_occlusion: Some(AmbientOcclusion { distance: 0.25, multiply: true }),
+        ..SolverOptions::default()
+    };
+    let mesh = bake_mesh(voxel_to_mesh(&voxels, None), &options);
+    let floor = |x: usize, z: usize| mesh.lit_faces.iter().position(|face| {
+        let min_x = face.corners.iter().map(|corner| corner.x).fold(f32::INFINITY, f32::min);
+        let min_z = face.corners.iter().map(|corner| corner.z).fold(f32::INFINITY, f32::min);
+        face.normal.y < 0. && face.corners.iter().all(|corner| corner.y == 0.125) && min_x == x as f32 / 8. && min_z == z as f32 / 8.
+    }).unwrap();
+    let open = |face: usize| mesh.lit_faces[face].brightness[0] / 0.5;
+
+    let (beside_mirror, between_pillars) = (floor(0, 4), floor(5, 4));
+    assert_eq!(open(beside_mirror), open(between_pillars));
+    // Without the mirrored copy the first column would see past the mirror plane
+    let faces = voxel_to_mesh(&voxels, None).faces;
+    assert!(open(beside_mirror) < ambient_occlusion(&faces, &faces, 0.25)[beside_mirror] - 0.02);
+}
@@ -0,0 +1,62 @@
+mod common;
+
+use common::bake;
+use voxel_generator::{PlyEncoding, write_ply};
+
+fn ply(encoding: PlyEncoding) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    write_ply(&bake(None), encoding, &mut bytes).unwrap();
+    bytes
+}
+
+/// The header and the data after it.
+fn split_header(bytes: &[u8]) -> (String, &[u8]) {
+    let end = bytes.windows(11).position(|window| window == b"end_header\n").unwrap() + 11;
+    (String::from_utf8(bytes[..end].to_vec()).unwrap(), &bytes[end..])
+}
+
+fn element_count(header: &str, element: &str) -> usize {
+    let prefix = format!("element {} ", element);
+    header.lines().find_map(|line| line.strip_prefix(&prefix)).unwrap().parse().unwrap()
+}
+
+#[test]
+fn binary_size_matches_header() {
+    let bytes = ply(PlyEncoding::BinaryLittleEndian);
+    let (header, data) = split_header(&bytes);
+    assert!(header.contains("format binary_little_endian 1.0\n"));
+
+    let vertices = element_count(&header, "vertex");
+    let faces = element_count(&header, "face");
+    assert!(vertices > 0 && faces > 0);
+    assert_eq!(data.len(), vertices * (6 * 4 + 3) + faces * (1 + 3 * 4));
+}
+
+#[test]
+fn ascii_has_a_line_per_element() {
+    let bytes = ply(PlyEncoding::Ascii);
+    let (header, data) = split_header(&bytes);
+    let data = String::from_utf8(data.to_vec()).unwrap();
+    let vertices = element_count(&header, "vertex");
+    let faces = element_count(&header, "face");
+
+    let lines: Vec<&str> = data.lines().collect();
+    assert_eq!(lines.len(), vertices + faces);
+    assert!(lines[..vertices].iter().all(|line| line.split(' ').count() == 9));
+    for face in &lines[vertices..] {
+        let values: Vec<usize> = face.split(' ').map(|value| value.parse().unwrap()).collect();
+        assert_eq!(values[0], 3);
+        assert!(values[1..].iter().all(|index| *index < vertices));
+    }
+}
+
+#[test]
+fn light_is_baked_into_colours() {
+    let bytes = ply(PlyEncoding::Ascii);
+    let (header, data) = split_header(&bytes);
+    let data = String::from_utf8(data.to_vec()).unwrap();
+    let colors: Vec<u8> = data.lines().take(element_count(&header, "vertex"))
+        .flat_map(|line| line.split(' ').skip(6).map(|value| value.parse::<u8>().unwrap()).collect::<Vec<u8>>())
+        .collect();
+    assert!(colors.iter().any(|color| *color > 0));
+}
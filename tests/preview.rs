@@ -0,0 +1,129 @@
+mod common;
+
+use common::bake;
+use image::{Rgba, RgbaImage};
+use voxel_generator::preview::{Camera, contact_sheet, render, Sampling, turntable};
+use voxel_generator::vector::Vec3;
+use voxel_generator::Palette;
+
+/// Looks straight down at the floor of the lit room, which lies in the Z plane.
+fn above() -> Camera {
+    Camera {
+        position: Vec3 { x: 0.5, y: 0.5, z: 2. },
+        target: Vec3 { x: 0.5, y: 0.5, z: 0. },
+        fov: 60_f32.to_radians(),
+        width: 64,
+        height: 48,
+    }
+}
+
+#[test]
+fn renders_at_the_size_of_the_camera() {
+    let image = render(&bake(None), &above(), Sampling::Nearest);
+    assert_eq!(image.dimensions(), (64, 48));
+}
+
+#[test]
+fn the_background_stays_transparent() {
+    let image = render(&bake(None), &above(), Sampling::Nearest);
+    assert_eq!(image.get_pixel(0, 0)[3], 0);
+    assert_eq!(image.get_pixel(32, 24)[3], 255);
+}
+
+#[test]
+fn nearer_faces_hide_the_ones_behind() {
+    let image = render(&bake(None), &above(), Sampling::Nearest);
+    // The emitting voxel stands on the floor, its top is white while the floor is grey
+    let project = |x: f32, y: f32| {
+        let camera = above();
+        let focal = 1. / (camera.fov * 0.5).tan();
+        let distance = camera.position.z - 0.5;
+        let right = (x - 0.5) / distance * focal * camera.height as f32 / camera.width as f32;
+        let down = (0.5 - y) / distance * focal;
+        (((right + 1.) * 0.5 * camera.width as f32) as u32, ((down + 1.) * 0.5 * camera.height as f32) as u32)
+    };
+    let (x, y) = project(0.375, 0.625);
+    let top = image.get_pixel(x, y);
+    let (x, y) = project(0.875, 0.125);
+    let floor = image.get_pixel(x, y);
+    assert!(top[0] > floor[0], "{:?} {:?}", top, floor);
+}
+
+#[test]
+fn bilinear_sampling_blends_neighbouring_pixels() {
+    let mesh = bake(None);
+    let nearest = render(&mesh, &above(), Sampling::Nearest);
+    let bilinear = render(&mesh, &above(), Sampling::Bilinear);
+    assert_ne!(nearest, bilinear);
+    assert_eq!(nearest.get_pixel(0, 0), bilinear.get_pixel(0, 0));
+}
+
+#[test]
+fn palette_meshes_use_the_colormap() {
+    let image = render(&bake(Some(&Palette::default())), &above(), Sampling::Nearest);
+    assert!(image.pixels().any(|pixel| *pixel != Rgba([0, 0, 0, 0]) && pixel[0] > 0));
+}
+
+#[test]
+fn cameras_inside_the_mesh_clip_faces_behind_them() {
+    let camera = Camera { position: Vec3 { x: 0.5, y: 0.5, z: 0.3 }, ..above() };
+    let image = render(&bake(None), &camera, Sampling::Nearest);
+    assert!(image.pixels().any(|pixel| pixel[3] == 255));
+}
+
+#[test]
+fn cameras_can_look_straight_down() {
+    let camera = Camera { position: Vec3 { x: 0.5, y: 2., z: 0.5 }, target: Vec3 { x: 0.5, y: 0., z: 0.5 }, ..above() };
+    let image = render(&bake(None), &camera, Sampling::Nearest);
+    assert!(image.pixels().any(|pixel| pixel[3] == 255));
+}
+
+#[test]
+fn the_order_of_the_triangles_does_not_matter() {
+    let camera = Camera { position: Vec3 { x: 1.6, y: 1.2, z: 1.4 }, target: Vec3 { x: 0.4, y: 0.5, z: 0.2 }, ..above() };
+    let mut mesh = bake(None);
+    let forward = render(&mesh, &camera, Sampling::Nearest);
+    let triangles: Vec<&[usize]> = mesh.mesh.indices.chunks_exact(3).rev().collect();
+    mesh.mesh.indices = triangles.concat();
+    assert_eq!(forward, render(&mesh, &camera, Sampling::Nearest));
+}
+
+#[test]
+fn turntables_circle_the_target() {
+    let camera = Camera { position: Vec3 { x: 1.5, y: 1., z: 0.5 }, ..above() };
+    let cameras = turntable(&camera, 4);
+    assert_eq!(cameras.len(), 4);
+    let distance = |camera: &Camera| camera.position.distance_squared(&camera.target);
+    for turned in &cameras {
+        assert!((distance(turned) - distance(&camera)).abs() < 1e-5);
+        assert_eq!(turned.position.y, camera.position.y);
+    }
+    assert!(cameras[0].position.distance_squared(&camera.position) < 1e-10);
+    // Half way round is on the other side of the target
+    assert!((cameras[2].position.x + camera.position.x - 2. * camera.target.x).abs() < 1e-5);
+}
+
+#[test]
+fn contact_sheets_have_a_labelled_row_for_every_tile() {
+    let image = |color| RgbaImage::from_pixel(10, 20, Rgba(color));
+    let rows = [
+        ("first", vec![image([255, 0, 0, 255]), image([0, 255, 0, 255])]),
+        ("second_tile", vec![image([0, 0, 255, 255]), image([0, 0, 0, 0])]),
+    ];
+    let sheet = contact_sheet(&rows);
+    assert_eq!(sheet.height() % 2, 0);
+    assert!(sheet.width() >= "second_tile".len() as u32 * 10);
+
+    let row_height = sheet.height() / 2;
+    let colors: Vec<Rgba<u8>> = sheet.pixels().copied().collect();
+    for color in [Rgba([255, 0, 0, 255]), Rgba([0, 255, 0, 255]), Rgba([0, 0, 255, 255])] {
+        assert!(colors.contains(&color));
+    }
+    // The transparent render leaves the background, and the labels are drawn above the images
+    assert!(sheet.pixels().all(|pixel| pixel[3] == 255));
+    let label_pixels = |top: u32| (0..sheet.width()).flat_map(|x| (top..top + 24).map(move |y| (x, y)))
+        .filter(|(x, y)| sheet.get_pixel(*x, *y)[0] > 200 && sheet.get_pixel(*x, *y)[1] > 200)
+        .count();
+    assert!(label_pixels(0) > 0);
+    assert!(label_pixels(row_height) > label_pixels(0));
+}
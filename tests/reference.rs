@@ -0,0 +1,66 @@
+mod common;
+
+use common::{EMPTY, floor_face};
+use voxel_generator::reference::{Comparison, solver_irradiance, trace_irradiance, TraceOptions};
+use voxel_generator::test_scenes::emitter_over_plane;
+use voxel_generator::vector::Vec3;
+use voxel_generator::{Light, Sky, SolverOptions, Sun, voxel_to_mesh};
+
+#[test]
+fn the_same_seed_gives_the_same_estimates() {
+    let voxels = emitter_over_plane(3);
+    let faces = voxel_to_mesh(&voxels, None).faces;
+    let (lighting, options) = (SolverOptions::default(), TraceOptions { samples: 16, bounces: 2, seed: 7 });
+    assert_eq!(trace_irradiance(&voxels, &faces, &lighting, &options), trace_irradiance(&voxels, &faces, &lighting, &options));
+    assert_ne!(trace_irradiance(&voxels, &faces, &lighting, &options), trace_irradiance(&voxels, &faces, &lighting, &TraceOptions { seed: 8, ..options }));
+}
+
+#[test]
+fn nothing_is_lit_without_emitters() {
+    let mut voxels = emitter_over_plane(3);
+    voxels[12][4][12].emission = 0.;
+    let faces = voxel_to_mesh(&voxels, None).faces;
+    let irradiance = trace_irradiance(&voxels, &faces, &SolverOptions::default(), &TraceOptions { samples: 16, ..TraceOptions::default() });
+    assert!(irradiance.iter().all(|light| *light == [0.; 3]));
+}
+
+#[test]
+fn direct_light_matches_the_solver_below_a_small_emitter() {
+    let voxels = emitter_over_plane(3);
+    let mesh = voxel_to_mesh(&voxels, None);
+    let below = floor_face(&mesh.faces, 12, 12);
+    let reference = trace_irradiance(&voxels, &mesh.faces[below..=below], &SolverOptions::default(), &TraceOptions { samples: 20000, bounces: 1, seed: 1 })[0][0];
+    let solver = solver_irradiance(&mesh, &SolverOptions { iterations: 1, ..SolverOptions::default() })[below][0];
+    assert!(reference > 0.);
+    assert!((solver - reference).abs() < 0.15 * reference, "solver {} reference {}", solver, reference);
+}
+
+#[test]
+fn lights_sky_and_sun_reach_both_the_solver_and_the_reference() {
+    let mut voxels = emitter_over_plane(3);
+    voxels[12][4][12] = EMPTY;
+    let mesh = voxel_to_mesh(&voxels, None);
+    let below = floor_face(&mesh.faces, 12, 12);
+    let lighting = SolverOptions {
+        iterations: 1,
+        lights: vec![Light::Point { position: Vec3 { x: 0.78, y: 0.5, z: 0.78 }, color: [0.1; 3] }],
+        sky: Some(Sky { zenith: [1.; 3], horizon: [1.; 3] }),
+        sun: Some(Sun { direction: Vec3 { x: 0., y: -1., z: 0. }, color: [0.5; 3] }),
+        ..SolverOptions::default()
+    };
+    let reference = trace_irradiance(&voxels, &mesh.faces[below..=below], &lighting, &TraceOptions { samples: 256, bounces: 1, seed: 1 })[0][0];
+    let solver = solver_irradiance(&mesh, &lighting)[below][0];
+    // The point light alone gives about 0.5, and the sky and sun 1.5 together
+    assert!(reference > 1.8);
+    assert!((solver - reference).abs() < 0.05 * reference, "solver {} reference {}", solver, reference);
+}
+
+#[test]
+fn the_report_lists_the_worst_faces() {
+    let comparison = Comparison::new(&emitter_over_plane(3), &SolverOptions::default(), &TraceOptions { samples: 8, bounces: 1, seed: 1 });
+    let report = comparison.report(3);
+    assert!(report.contains(&format!("faces: {}", comparison.faces.len())));
+    assert!(report.contains("energy ratio"));
+    let worst = report.lines().skip_while(|line| !line.starts_with("worst faces")).skip(2).count();
+    assert_eq!(worst, 3);
+}
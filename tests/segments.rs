@@ -0,0 +1,148 @@
+use image::{Rgba, RgbaImage};
+use voxel_generator::{segment, SegmentShape, sweep_segment, SweepPath, VoxelGrid};
+use voxel_generator::vector::Vec2;
+
+/// Layers whose pixels are coloured by where they come from: the red channel is the layer, the
+/// green channel the column and the blue channel the height.
+fn coded_layers() -> [RgbaImage; 16] {
+    std::array::from_fn(|layer| RgbaImage::from_fn(16, 16, |column, row| Rgba([layer as u8, column as u8, 15 - row as u8, 255])))
+}
+
+/// The layer, column and height a voxel was copied from, or `None` when it is empty.
+fn source<const SIZE: usize>(voxels: &VoxelGrid<SIZE>, x: usize, y: usize, z: usize) -> Option<(usize, usize, usize)> {
+    let color = voxels[x][y][z].color;
+    (color[3] != 0).then(|| (color[0] as usize, color[1] as usize, color[2] as usize))
+}
+
+/// Checks that every voxel of `shape` is copied from the layer and column given by `expected`,
+/// shifted up by its rise.
+fn assert_shape(shape: SegmentShape, expected: impl Fn(usize, usize) -> (usize, usize, usize)) {
+    let voxels: VoxelGrid<16> = segment(&coded_layers(), shape, 1.);
+    for x in 0..16 {
+        for z in 0..16 {
+            let (layer, column, rise) = expected(x, z);
+            for y in 0..16 {
+                let expected = (y >= rise).then_some((layer, column, y.wrapping_sub(rise)));
+                assert_eq!(source(&voxels, x, y, z), expected, "{:?} at {} {} {}", shape, x, y, z);
+            }
+        }
+    }
+}
+
+fn fold(coordinate: usize) -> usize {
+    coordinate.max(15 - coordinate)
+}
+
+#[test]
+fn edge_pieces_use_the_columns_as_is() {
+    assert_shape(SegmentShape::Straight, |x, z| (x, z, 0));
+    assert_shape(SegmentShape::LeftCurve, |x, z| if x < z { (x, z, 0) } else { (z, x, 0) });
+    assert_shape(SegmentShape::RightCurve, |x, z| if x > z { (x, z, 0) } else { (z, x, 0) });
+}
+
+#[test]
+fn corridor_mirrors_the_wall_to_both_sides() {
+    assert_shape(SegmentShape::Corridor, |x, z| (x, fold(z), 0));
+
+    let voxels: VoxelGrid<16> = segment(&coded_layers(), SegmentShape::Corridor, 1.);
+    assert_eq!(source(&voxels, 3, 0, 0), Some((3, 15, 0)));
+    assert_eq!(source(&voxels, 3, 0, 15), Some((3, 15, 0)));
+    assert_eq!(source(&voxels, 3, 0, 7), Some((3, 8, 0)));
+    assert_eq!(source(&voxels, 3, 0, 8), Some((3, 8, 0)));
+}
+
+#[test]
+fn junctions_keep_the_more_open_hallway() {
+    let open = |x: usize, z: usize| if fold(z) <= fold(x) { (x, fold(z), 0) } else { (z, fold(x), 0) };
+    assert_shape(SegmentShape::Crossing, open);
+    assert_shape(SegmentShape::TJunction, |x, z| if z < 8 { (x, fold(z), 0) } else { open(x, z) });
+
+    // The branch only leaves towards positive Z, the other side keeps its wall
+    let voxels: VoxelGrid<16> = segment(&coded_layers(), SegmentShape::TJunction, 1.);
+    assert_eq!(source(&voxels, 8, 0, 0).map(|source| source.1), Some(15));
+    assert_eq!(source(&voxels, 8, 0, 15).map(|source| source.1), Some(8));
+}
+
+#[test]
+fn dead_end_keeps_the_more_closed_hallway() {
+    assert_shape(SegmentShape::DeadEnd, |x, z| {
+        if x < 8 || fold(z) >= fold(x) { (x, fold(z), 0) } else { (z, fold(x), 0) }
+    });
+
+    let voxels: VoxelGrid<16> = segment(&coded_layers(), SegmentShape::DeadEnd, 1.);
+    assert_eq!(source(&voxels, 15, 0, 8).map(|source| source.1), Some(15));
+    assert_eq!(source(&voxels, 0, 0, 8).map(|source| source.1), Some(8));
+}
+
+#[test]
+fn ramp_and_stairs_rise_along_x() {
+    assert_shape(SegmentShape::Ramp, |x, z| (x, fold(z), x / 2));
+    assert_shape(SegmentShape::Stairs, |x, z| (x, fold(z), x / 4 * 2));
+}
+
+#[test]
+fn diagonal_folds_around_the_diagonal() {
+    assert_shape(SegmentShape::Diagonal, |x, z| ((x + z) / 2, 8 + x.abs_diff(z) / 2, 0));
+}
+
+#[test]
+fn shapes_fold_around_the_middle_of_smaller_grids() {
+    assert_eq!(SegmentShape::Corridor.sample(2, 0, 8).column, 7);
+    assert_eq!(SegmentShape::Corridor.sample(2, 3, 8).column, 4);
+    assert_eq!(SegmentShape::Corridor.sample(2, 4, 8).column, 4);
+    assert_eq!(SegmentShape::Diagonal.sample(5, 5, 8).column, 4);
+
+    let voxels: VoxelGrid<8> = segment(&coded_layers(), SegmentShape::Corridor, 1.);
+    for z in 0..8 {
+        assert_eq!(source(&voxels, 2, 0, z), Some((2, z.max(7 - z), 0)));
+    }
+}
+
+#[test]
+fn straight_sweep_matches_the_corridor() {
+    let path = SweepPath::Polyline(vec![Vec2 { x: 0., y: 8. }, Vec2 { x: 16., y: 8. }]);
+    let swept: VoxelGrid<16> = sweep_segment(&coded_layers(), &path, 1.);
+    let corridor: VoxelGrid<16> = segment(&coded_layers(), SegmentShape::Corridor, 1.);
+    for x in 0..16 {
+        for z in 0..16 {
+            assert_eq!(source(&swept, x, 0, z), source(&corridor, x, 0, z), "at {} {}", x, z);
+        }
+    }
+}
+
+#[test]
+fn sweep_follows_a_corner() {
+    let path = SweepPath::Polyline(vec![Vec2 { x: 0., y: 8. }, Vec2 { x: 8., y: 8. }, Vec2 { x: 8., y: 16. }]);
+    let voxels: VoxelGrid<16> = sweep_segment(&coded_layers(), &path, 1.);
+    // Along the first leg the columns are the distance from the path, the layers the distance along it
+    assert_eq!(source(&voxels, 2, 0, 3), Some((2, 12, 0)));
+    assert_eq!(source(&voxels, 2, 0, 12), Some((2, 12, 0)));
+    // Along the second leg the same holds for X
+    assert_eq!(source(&voxels, 10, 0, 13), Some((13, 10, 0)));
+    assert_eq!(source(&voxels, 5, 0, 13), Some((13, 10, 0)));
+    // Around the corner the closest point is the corner itself
+    assert_eq!(source(&voxels, 12, 0, 4), Some((8, 13, 0)));
+    // Further than the layers are wide is left empty
+    assert_eq!(source(&voxels, 15, 0, 0), None);
+}
+
+#[test]
+fn sweep_follows_a_spline() {
+    let path = SweepPath::Spline(vec![Vec2 { x: 0., y: 8. }, Vec2 { x: 8., y: 8. }, Vec2 { x: 8., y: 16. }]);
+    let voxels: VoxelGrid<16> = sweep_segment(&coded_layers(), &path, 1.);
+    // The ends of the path get the first and last layer, next to the path the middle column
+    assert_eq!(source(&voxels, 0, 0, 8), Some((0, 8, 0)));
+    assert_eq!(source(&voxels, 8, 0, 15), Some((15, 8, 0)));
+    assert_eq!(source(&voxels, 15, 0, 0), None);
+
+    // Walking along the middle of the curve only ever moves forward through the layers
+    let middle: Vec<usize> = (0..16).map(|i| source(&voxels, i.min(8), 0, if i < 8 { 8 } else { i }).unwrap().0).collect();
+    assert!(middle.windows(2).all(|pair| pair[0] <= pair[1]), "{:?}", middle);
+    for x in 0..16 {
+        for z in 0..16 {
+            if let Some((_, column, _)) = source(&voxels, x, 0, z) {
+                assert!((8..16).contains(&column), "column {} at {} {}", column, x, z);
+            }
+        }
+    }
+}
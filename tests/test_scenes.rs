@@ -0,0 +1,133 @@
+mod common;
+
+use common::floor_face;
+use std::f32::consts::PI;
+use voxel_generator::radiosity::Face;
+use voxel_generator::reference::{solver_irradiance, trace_irradiance, TraceOptions};
+use voxel_generator::test_scenes::{cornell_box, emitter_over_plane, furnace, furnace_irradiance};
+use voxel_generator::{SolverOptions, voxel_to_mesh};
+
+const FACE_AREA: f32 = 1. / 256.;
+
+/// Light given off by all faces together, each as bright as it starts out.
+fn emitted_power(faces: &[Face]) -> f32 {
+    faces.iter().map(|face| face.brightness[0] * FACE_AREA).sum()
+}
+
+fn received_power(irradiance: &[[f32; 3]]) -> f32 {
+    irradiance.iter().map(|light| light[0] * FACE_AREA).sum()
+}
+
+/// Only the emitting voxels, bounced `iterations` times.
+fn emitters(iterations: u8) -> SolverOptions {
+    SolverOptions { iterations, ..SolverOptions::default() }
+}
+
+#[test]
+fn the_reference_converges_in_the_furnace() {
+    let voxels = furnace(128, 1.);
+    let faces = voxel_to_mesh(&voxels, None).faces;
+    for bounces in 1..=4 {
+        let expected = furnace_irradiance(128, 1., bounces);
+        let irradiance = trace_irradiance(&voxels, &faces, &SolverOptions::default(), &TraceOptions { samples: 4, bounces, seed: 1 });
+        for light in irradiance {
+            assert!((light[0] - expected).abs() < 1e-3 * expected, "{} bounces: {} instead of {}", bounces, light[0], expected);
+        }
+    }
+}
+
+#[test]
+fn the_furnace_stays_below_its_limit() {
+    let limit = 0.5 / (1. - 0.5);
+    let values: Vec<f32> = (1..=20).map(|bounces| furnace_irradiance(128, 1., bounces)).collect();
+    assert!(values.windows(2).all(|pair| pair[0] < pair[1]));
+    assert!(values.iter().all(|value| *value < limit));
+    assert!((values[19] - limit).abs() < 1e-4);
+}
+
+#[test]
+fn the_solver_matches_the_furnace() {
+    let mesh = voxel_to_mesh(&furnace(128, 1.), None);
+    for bounces in [1, 3] {
+        let expected = furnace_irradiance(128, 1., bounces);
+        let light: Vec<f32> = solver_irradiance(&mesh, &emitters(bounces)).iter().map(|light| light[0]).collect();
+        // Treating the faces as points overestimates the light between faces close to each
+        // other, most of all in the edges and corners, while the mean stays close
+        let mean = light.iter().sum::<f32>() / light.len() as f32;
+        assert!((mean - expected).abs() < 0.1 * expected, "{} bounces: mean {} instead of {}", bounces, mean, expected);
+        for light in light {
+            assert!((light - expected).abs() < 0.4 * expected, "{} bounces: {} instead of {}", bounces, light, expected);
+        }
+    }
+}
+
+#[test]
+fn mirrored_copies_only_light_the_tile_where_it_is_open() {
+    let open = emitter_over_plane(4);
+    let mut walled = open;
+    for column in walled[0].iter_mut() {
+        *column = [open[0][0][0]; 16];
+    }
+    // Next to the X plane, lit by the copy mirrored across it unless the wall is in the way
+    let light = |voxels| {
+        let mesh = voxel_to_mesh(voxels, None);
+        solver_irradiance(&mesh, &emitters(1))[floor_face(&mesh.faces, 1, 12)][0]
+    };
+    assert!(light(&open) > 1.5 * light(&walled), "{} open, {} walled", light(&open), light(&walled));
+}
+
+#[test]
+fn the_reference_conserves_energy() {
+    let voxels = cornell_box();
+    let faces = voxel_to_mesh(&voxels, None).faces;
+    let irradiance = trace_irradiance(&voxels, &faces, &SolverOptions::default(), &TraceOptions { samples: 64, bounces: 1, seed: 1 });
+    let (emitted, received) = (emitted_power(&faces), received_power(&irradiance));
+    // The front of the box is open, so some of the light leaves it
+    assert!(received < emitted, "{} received of {} emitted", received, emitted);
+    assert!(received > 0.5 * emitted, "{} received of {} emitted", received, emitted);
+}
+
+#[test]
+fn the_solver_matches_the_form_factor_over_a_plane() {
+    let mesh = voxel_to_mesh(&emitter_over_plane(4), None);
+    let below = floor_face(&mesh.faces, 12, 12);
+    // Of a square with sides `2 * half` centred `distance` above a point, facing it
+    let (half, distance): (f32, f32) = (0.5 / 16., 4. / 16.);
+    let x = half / distance / (1. + (half / distance).powi(2)).sqrt();
+    let form_factor = 4. / PI * x * x.atan();
+    let expected = 255. / 256. * form_factor;
+
+    let solver = solver_irradiance(&mesh, &emitters(1))[below][0];
+    assert!((solver - expected).abs() < 0.05 * expected, "{} instead of {}", solver, expected);
+}
+
+#[test]
+fn direct_light_falls_off_with_the_square_of_the_distance() {
+    for height in [3, 6] {
+        let voxels = emitter_over_plane(height);
+        let mesh = voxel_to_mesh(&voxels, None);
+        let below = floor_face(&mesh.faces, 12, 12);
+        // The bottom of the emitting voxel seen from straight below, as if it were a point
+        let distance = height as f32 / 16.;
+        let expected = 255. / 256. * FACE_AREA / (PI * distance * distance);
+
+        let reference = trace_irradiance(&voxels, &mesh.faces[below..=below], &SolverOptions::default(), &TraceOptions { samples: 20000, bounces: 1, seed: 1 })[0][0];
+        let solver = solver_irradiance(&mesh, &emitters(1))[below][0];
+        for (name, value) in [("reference", reference), ("solver", solver)] {
+            assert!((value - expected).abs() < 0.15 * expected, "{} at {} voxels: {} instead of {}", name, height, value, expected);
+        }
+    }
+}
+
+#[test]
+fn the_cornell_box_bleeds_colour_onto_the_floor() {
+    let voxels = cornell_box();
+    let mesh = voxel_to_mesh(&voxels, None);
+    let (near_red, near_green) = (floor_face(&mesh.faces, 1, 13), floor_face(&mesh.faces, 14, 13));
+    let samples = [near_red, near_green].map(|face| trace_irradiance(&voxels, &mesh.faces[face..=face], &SolverOptions::default(), &TraceOptions { samples: 4000, bounces: 2, seed: 1 })[0]);
+    let solver = solver_irradiance(&mesh, &emitters(2));
+    for irradiance in [samples, [solver[near_red], solver[near_green]]] {
+        assert!(irradiance[0][0] > irradiance[0][1], "{:?}", irradiance);
+        assert!(irradiance[1][1] > irradiance[1][0], "{:?}", irradiance);
+    }
+}